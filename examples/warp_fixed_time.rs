@@ -1,4 +1,4 @@
-use kalman_fusion::{kalman_update_fixed, KalmanState};
+use kalman_fusion::{fuse_batch, KalmanState};
 
 use chrono::Utc;
 use fixed::types::U32F32;
@@ -14,6 +14,7 @@ use rand_distr::Distribution;
 fn main() {
     const NUM_SENSORS: usize = 8;
     const MAX_TIME_STEPS: u32 = 1_000;
+    const SENSOR_VARIANCE: f64 = 1E-6;
 
     let start_now = Utc::now();
     let base_start_timestamp = start_now.timestamp() as u32;
@@ -27,10 +28,13 @@ fn main() {
         let mut sensor_values: [u32; NUM_SENSORS] = [start_timestamp; NUM_SENSORS];
         let mut internal_sensor_values: [f64; NUM_SENSORS] = [start_timestamp as f64; NUM_SENSORS];
 
+        let sensor_variance = FixedType::from_num(SENSOR_VARIANCE);
+        let mut readings = [(FixedType::from_num(start_timestamp), sensor_variance); NUM_SENSORS];
+
         let mut kstate = KalmanState::new_fixed(
             FixedType::from_num(start_timestamp),
             FixedType::from_num(1E-3),
-            FixedType::from_num(1E-6),
+            sensor_variance,
              FixedType::from_num(1E-6),
             );
 
@@ -42,8 +46,10 @@ fn main() {
                 internal_sensor_values[j] += rand_blip;
                 // but the value readable external to the sensor is an integer
                 sensor_values[j] = internal_sensor_values[j].round() as u32;
-                kstate = kalman_update_fixed(&kstate, FixedType::from_num(sensor_values[j]));
+                readings[j] = (FixedType::from_num(sensor_values[j]), sensor_variance);
             }
+            // fuse the simultaneous readings as a single update
+            kstate = fuse_batch(&kstate, &readings);
         }
 
         let true_val = (start_timestamp + MAX_TIME_STEPS) as f64;
@@ -1,4 +1,4 @@
-use kalman_fusion::{kalman_update_float, KalmanState};
+use kalman_fusion::{fuse_batch, KalmanState};
 
 use chrono::Utc;
 use rand::prelude::*;
@@ -13,6 +13,7 @@ use rand_distr::Distribution;
 fn main() {
     const NUM_SENSORS: usize = 8;
     const MAX_TIME_STEPS: u32 = 1_000;
+    const SENSOR_VARIANCE: f64 = 1E-6;
 
     let start_now = Utc::now();
     let base_start_timestamp: i64 = start_now.timestamp();
@@ -23,12 +24,14 @@ fn main() {
         let start_timestamp: f64 = (base_start_timestamp + trial * 100) as f64;
 
         let mut sensor_values: [f64; NUM_SENSORS] = [start_timestamp; NUM_SENSORS];
-        let mut internal_sensor_values: [f64; NUM_SENSORS] = [start_timestamp as f64; NUM_SENSORS];
+        let mut internal_sensor_values: [f64; NUM_SENSORS] = [start_timestamp; NUM_SENSORS];
+
+        let mut readings: [(f64, f64); NUM_SENSORS] = [(start_timestamp, SENSOR_VARIANCE); NUM_SENSORS];
 
         let mut kstate = KalmanState::new_float (
             start_timestamp,
             1E-3,
-            1E-6,
+            SENSOR_VARIANCE,
            1E-6,
         );
 
@@ -40,8 +43,10 @@ fn main() {
                 internal_sensor_values[j] += rand_blip;
                 // but the value readable external to the sensor is an integer
                 sensor_values[j] = internal_sensor_values[j].round();
-                kstate = kalman_update_float(&kstate, sensor_values[j]);
+                readings[j] = (sensor_values[j], SENSOR_VARIANCE);
             }
+            // fuse the simultaneous readings as a single update
+            kstate = fuse_batch(&kstate, &readings);
         }
 
         let true_val = start_timestamp + MAX_TIME_STEPS as f64;
@@ -0,0 +1,371 @@
+use crate::scalar::blend;
+use crate::{KalmanState, Scalar, UpdateReport};
+
+/// Limits and memory length for re-estimating a noise variance online
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "NoiseAdaptationData<T>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct NoiseAdaptation<T> {
+  /// Approximate number of recent innovations the estimate is averaged over
+  pub memory: u32,
+  /// Smallest variance the estimate may take
+  pub min_variance: T,
+  /// Largest variance the estimate may take
+  pub max_variance: T,
+}
+
+/// Unvalidated `NoiseAdaptation` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct NoiseAdaptationData<T> {
+  memory: u32,
+  min_variance: T,
+  max_variance: T,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<NoiseAdaptationData<T>> for NoiseAdaptation<T>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: NoiseAdaptationData<T>) -> Result<Self, Self::Error> {
+    if data.min_variance < T::zero() {
+      return Err("negative variance bound");
+    }
+    if data.min_variance > data.max_variance {
+      return Err("min_variance exceeds max_variance");
+    }
+    Ok(NoiseAdaptation {
+      memory: data.memory,
+      min_variance: data.min_variance,
+      max_variance: data.max_variance,
+    })
+  }
+}
+
+impl<T> NoiseAdaptation<T>
+  where T: Scalar
+{
+  /// The bounds may be given in either order
+  pub fn new(memory: u32, min_variance: T, max_variance: T) -> Self {
+    let (min_variance, max_variance) = (min_variance.abs(), max_variance.abs());
+    let (min_variance, max_variance) =
+      if min_variance <= max_variance { (min_variance, max_variance) }
+      else { (max_variance, min_variance) };
+    NoiseAdaptation {
+      memory: memory.max(1),
+      min_variance,
+      max_variance,
+    }
+  }
+
+  /// Clamp `variance` into `[min_variance, max_variance]`
+  fn clamp(&self, variance: T) -> T {
+    if variance < self.min_variance { self.min_variance }
+    else if variance > self.max_variance { self.max_variance }
+    else { variance }
+  }
+
+  /// Averaging weight for the next sample: a running mean until `memory`
+  /// samples have been seen, then an exponential window of that length.
+  /// A `memory` of zero (possible via the public field) acts as one.
+  fn weight(&self, count: u32) -> T {
+    T::one() / T::from_f64(count.min(self.memory).max(1) as f64)
+  }
+}
+
+/// A single-variable filter that re-estimates its noise variances online
+/// from the innovation sequence (Sage-Husa style covariance matching).
+///
+/// Measurement adaptation: each innovation `e = observation - estimate` has
+/// expected variance `uncertainty + measurement_variance`, so the measurement
+/// variance is estimated as the windowed mean of `e^2` less the prior uncertainty.
+///
+/// Process adaptation: the correction `gain * e` applied by each update
+/// reflects how far the variable actually moved, so the process variance
+/// (per unit of time) is estimated as the windowed mean of the squared
+/// correction divided by the time elapsed since the previous update.
+/// Persistently large innovations raise it; an over-conservative filter,
+/// whose corrections are small, lowers it.
+///
+/// Both estimates are clamped to their configured bounds. Adapting both at once
+/// is possible, but the two trade off against each other, so it works best
+/// when at least one is roughly known and tightly bounded.
+/// Squaring innovations can overflow narrow fixed-point types;
+/// prefer types with plenty of integer bits, such as `I32F32`.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct AdaptiveState<T> {
+  /// The underlying filter state, whose noise variances are adapted
+  pub state: KalmanState<T>,
+  measurement_adaptation: Option<NoiseAdaptation<T>>,
+  process_adaptation: Option<NoiseAdaptation<T>>,
+  innovation_power: T,     // windowed mean of the squared innovations
+  correction_power: T,     // windowed mean of the squared corrections per unit time
+  measurement_count: u32,  // samples seen, saturating at the memory length
+  process_count: u32,
+  elapsed: T,              // time predicted forward since the last update
+}
+
+impl<T> AdaptiveState<T>
+  where T: Scalar
+{
+  /// Wrap `state`, adapting its measurement variance
+  /// and using the configured value as the initial guess.
+  pub fn new(state: KalmanState<T>, measurement_adaptation: NoiseAdaptation<T>) -> Self {
+    AdaptiveState::with_adaptation(state, Some(measurement_adaptation), None)
+  }
+
+  /// Wrap `state`, adapting its process variance
+  /// and using the configured value as the initial guess.
+  pub fn with_process_adaptation(state: KalmanState<T>, process_adaptation: NoiseAdaptation<T>) -> Self {
+    AdaptiveState::with_adaptation(state, None, Some(process_adaptation))
+  }
+
+  /// Wrap `state`, adapting whichever noise variances have an adaptation configured
+  pub fn with_adaptation(
+    state: KalmanState<T>,
+    measurement_adaptation: Option<NoiseAdaptation<T>>,
+    process_adaptation: Option<NoiseAdaptation<T>>) -> Self
+  {
+    let mut state = state;
+    if let Some(adaptation) = measurement_adaptation {
+      state.measurement_variance = adaptation.clamp(state.measurement_variance);
+    }
+    if let Some(adaptation) = process_adaptation {
+      state.process_variance = adaptation.clamp(state.process_variance);
+    }
+    AdaptiveState {
+      state,
+      measurement_adaptation,
+      process_adaptation,
+      innovation_power: state.uncertainty + state.measurement_variance,
+      correction_power: state.process_variance,
+      measurement_count: 0,
+      process_count: 0,
+      elapsed: T::zero(),
+    }
+  }
+
+  /// The current estimate of the measurement variance
+  pub fn measurement_variance(&self) -> T {
+    self.state.measurement_variance
+  }
+
+  /// The current estimate of the process variance
+  pub fn process_variance(&self) -> T {
+    self.state.process_variance
+  }
+
+  pub fn measurement_adaptation(&self) -> Option<&NoiseAdaptation<T>> {
+    self.measurement_adaptation.as_ref()
+  }
+
+  pub fn process_adaptation(&self) -> Option<&NoiseAdaptation<T>> {
+    self.process_adaptation.as_ref()
+  }
+
+  /// Predict step: see `KalmanState::predict`
+  pub fn predict(&mut self) {
+    self.state.predict();
+    self.elapsed = self.elapsed + T::one();
+  }
+
+  /// Predict step with a variable time step: see `KalmanState::predict_dt`
+  pub fn predict_dt(&mut self, dt: T) {
+    self.state.predict_dt(dt);
+    self.elapsed = self.elapsed + dt.abs();
+  }
+
+  /// Update step: re-estimate the measurement variance from this
+  /// observation's innovation, incorporate the observation,
+  /// then re-estimate the process variance from the correction applied.
+  pub fn update(&mut self, observation: T) -> UpdateReport<T> {
+    let innovation = observation.abs_diff(self.state.estimate);
+
+    if let Some(adaptation) = self.measurement_adaptation {
+      self.measurement_count = (self.measurement_count + 1).min(adaptation.memory);
+      let weight = adaptation.weight(self.measurement_count);
+      self.innovation_power = blend(self.innovation_power, innovation * innovation, weight);
+
+      let prior_uncertainty = self.state.uncertainty;
+      let measured =
+        if self.innovation_power > prior_uncertainty { self.innovation_power - prior_uncertainty }
+        else { T::zero() };
+      self.state.measurement_variance = adaptation.clamp(measured);
+    }
+
+    let report = self.state.update(observation);
+
+    // with no time elapsed there is no process noise to attribute the correction to
+    if let Some(adaptation) = self.process_adaptation {
+      if self.elapsed > T::zero() {
+        self.process_count = (self.process_count + 1).min(adaptation.memory);
+        let weight = adaptation.weight(self.process_count);
+        let correction = report.gain * innovation;
+        let sample = correction * correction / self.elapsed;
+        self.correction_power = blend(self.correction_power, sample, weight);
+        self.state.process_variance = adaptation.clamp(self.correction_power);
+      }
+    }
+    self.elapsed = T::zero();
+    report
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+  use rand_distr::{Distribution, Normal};
+
+  #[test]
+  fn test_learns_measurement_variance_f64() {
+    // true measurement variance 0.25, initial guess 100x too small
+    let noise = Normal::new(0.0f64, 0.5).unwrap();
+    let mut rng = StdRng::seed_from_u64(21);
+    let kstate = KalmanState::new(0.0f64, 10.0, 0.0025, 1E-6);
+    let mut adaptive = AdaptiveState::new(kstate, NoiseAdaptation::new(500, 1E-4, 10.0));
+    for _i in 0..5000 {
+      adaptive.predict();
+      adaptive.update(5.0 + noise.sample(&mut rng));
+    }
+    println!("variance: {} estimate: {}", adaptive.measurement_variance(), adaptive.state.estimate);
+    assert!((adaptive.measurement_variance() - 0.25).abs() < 0.05);
+    assert!((adaptive.state.estimate - 5.0).abs() < 0.1);
+  }
+
+  #[test]
+  fn test_bounds_f64() {
+    let kstate = KalmanState::new(1.0f64, 1.0, 1.0, 0.0);
+    let mut adaptive = AdaptiveState::new(kstate, NoiseAdaptation::new(10, 0.01, 0.5));
+    // the initial guess is clamped into range
+    assert_eq!(adaptive.measurement_variance(), 0.5);
+    for _i in 0..100 {
+      adaptive.update(1.0);
+    }
+    // noiseless observations drive the estimate to the lower bound
+    assert_eq!(adaptive.measurement_variance(), 0.01);
+  }
+
+  #[test]
+  fn test_learns_measurement_variance_i32f32() {
+    type TestType = I32F32;
+    let kstate = KalmanState::new(
+      TestType::from_num(2),
+      TestType::from_num(1),
+      TestType::from_num(4),
+      TestType::from_num(1E-6),
+    );
+    let adaptation = NoiseAdaptation::new(
+      64, TestType::from_num(1E-3), TestType::from_num(10));
+    let mut adaptive = AdaptiveState::new(kstate, adaptation);
+    // alternating +/- 0.3 about the truth: variance 0.09
+    for i in 0..1000 {
+      let offset = TestType::from_num(0.3);
+      let observation = if i % 2 == 0 { TestType::from_num(2) + offset } else { TestType::from_num(2) - offset };
+      adaptive.predict();
+      adaptive.update(observation);
+    }
+    println!("variance: {}", adaptive.measurement_variance());
+    assert!((adaptive.measurement_variance() - TestType::from_num(0.09)).abs() < TestType::from_num(0.01));
+  }
+
+  #[test]
+  fn test_learns_process_variance_f64() {
+    // a random walk with process variance 1E-3 per step, initial guess 1000x too small
+    let step = Normal::new(0.0f64, 1E-3f64.sqrt()).unwrap();
+    let noise = Normal::new(0.0f64, 0.1).unwrap();
+    let mut rng = StdRng::seed_from_u64(22);
+    let kstate = KalmanState::new(0.0f64, 1.0, 0.01, 1E-6);
+    let mut adaptive = AdaptiveState::with_process_adaptation(kstate, NoiseAdaptation::new(1000, 1E-8, 1.0));
+    let mut truth = 0.0;
+    for _i in 0..20000 {
+      truth += step.sample(&mut rng);
+      adaptive.predict();
+      adaptive.update(truth + noise.sample(&mut rng));
+    }
+    println!("process variance: {}", adaptive.process_variance());
+    assert!((adaptive.process_variance() - 1E-3).abs() < 3E-4);
+    assert_eq!(adaptive.measurement_variance(), 0.01);
+  }
+
+  #[test]
+  fn test_over_conservative_process_f64() {
+    // a constant truth: an initial process variance of 1 is far too large
+    let noise = Normal::new(0.0f64, 0.1).unwrap();
+    let mut rng = StdRng::seed_from_u64(23);
+    let kstate = KalmanState::new(3.0f64, 1.0, 0.01, 1.0);
+    let mut adaptive = AdaptiveState::with_process_adaptation(kstate, NoiseAdaptation::new(100, 1E-6, 10.0));
+    for _i in 0..2000 {
+      adaptive.predict_dt(0.5);
+      adaptive.update(3.0 + noise.sample(&mut rng));
+    }
+    println!("process variance: {}", adaptive.process_variance());
+    assert!(adaptive.process_variance() < 1E-3);
+  }
+
+  #[test]
+  fn test_learns_process_variance_i32f32() {
+    type TestType = I32F32;
+    let step = Normal::new(0.0f64, 1E-2).unwrap();
+    let noise = Normal::new(0.0f64, 0.05).unwrap();
+    let mut rng = StdRng::seed_from_u64(24);
+    let kstate = KalmanState::new(
+      TestType::from_num(0),
+      TestType::from_num(1),
+      TestType::from_num(0.0025),
+      TestType::from_num(1E-6),
+    );
+    let adaptation = NoiseAdaptation::new(
+      500, TestType::from_num(1E-6), TestType::from_num(1));
+    let mut adaptive = AdaptiveState::with_process_adaptation(kstate, adaptation);
+    let mut truth = 0.0;
+    for _i in 0..10000 {
+      truth += step.sample(&mut rng);
+      adaptive.predict();
+      adaptive.update(TestType::from_num(truth + noise.sample(&mut rng)));
+    }
+    println!("process variance: {}", adaptive.process_variance());
+    assert!((adaptive.process_variance().to_num::<f64>() - 1E-4).abs() < 4E-5);
+  }
+
+  #[test]
+  fn test_zero_memory_i32f32() {
+    type TestType = I32F32;
+    let kstate = KalmanState::new(
+      TestType::from_num(1),
+      TestType::from_num(1),
+      TestType::from_num(1),
+      TestType::from_num(0),
+    );
+    // reversed bounds are reordered
+    let mut adaptation = NoiseAdaptation::new(4, TestType::from_num(2), TestType::from_num(0.1));
+    assert_eq!(adaptation.min_variance, TestType::from_num(0.1));
+    assert_eq!(adaptation.max_variance, TestType::from_num(2));
+
+    // bypassing the constructor's clamp must not divide by zero
+    adaptation.memory = 0;
+    let mut adaptive = AdaptiveState::with_adaptation(kstate, Some(adaptation), Some(adaptation));
+    adaptive.predict();
+    adaptive.update(TestType::from_num(2));
+    assert!(adaptive.measurement_variance() >= TestType::from_num(0.1));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_validates_bounds() {
+    let json = r#"{"memory":20,"min_variance":0.1,"max_variance":10.0}"#;
+    assert!(serde_json::from_str::<NoiseAdaptation<f64>>(json).is_ok());
+    let json = r#"{"memory":20,"min_variance":10.0,"max_variance":0.1}"#;
+    assert!(serde_json::from_str::<NoiseAdaptation<f64>>(json).is_err());
+    let json = r#"{"memory":20,"min_variance":-0.1,"max_variance":10.0}"#;
+    assert!(serde_json::from_str::<NoiseAdaptation<f64>>(json).is_err());
+  }
+}
@@ -0,0 +1,426 @@
+use crate::{KalmanError, KalmanState, Scalar};
+
+/// A recorded clock series, sampled at a fixed interval `tau0`
+#[derive(Debug, Clone, Copy)]
+pub enum ClockData<'a, T> {
+  /// Phase (time error) readings, in seconds
+  Phase(&'a [T]),
+  /// Fractional frequency readings, each averaged over one interval
+  Frequency(&'a [T]),
+}
+
+impl<T> ClockData<'_, T>
+  where T: Scalar
+{
+  /// Number of phase points represented by the series
+  fn phase_len(&self) -> usize {
+    match self {
+      ClockData::Phase(phase) => phase.len(),
+      ClockData::Frequency(frequency) => frequency.len() + 1,
+    }
+  }
+}
+
+/// Walks forward through the phase points of a series. Frequency data is
+/// integrated on the fly, so no phase buffer needs to be allocated.
+struct PhaseCursor<'a, T> {
+  data: ClockData<'a, T>,
+  tau0: f64,
+  index: usize,
+  value: f64,
+}
+
+impl<'a, T> PhaseCursor<'a, T>
+  where T: Scalar
+{
+  fn new(data: ClockData<'a, T>, tau0: f64, index: usize) -> Self {
+    let mut cursor = PhaseCursor { data, tau0, index: 0, value: 0.0 };
+    if let ClockData::Phase(phase) = data {
+      cursor.value = phase[0].to_f64();
+    }
+    cursor.advance(index);
+    cursor
+  }
+
+  fn advance(&mut self, steps: usize) {
+    match self.data {
+      ClockData::Phase(phase) => {
+        self.index += steps;
+        self.value = phase[self.index].to_f64();
+      }
+      ClockData::Frequency(frequency) => {
+        for y in &frequency[self.index..self.index + steps] {
+          self.value += self.tau0 * y.to_f64();
+        }
+        self.index += steps;
+      }
+    }
+  }
+}
+
+/// Walks forward through the second differences `x[i+2m] - 2 x[i+m] + x[i]`
+struct SecondDifference<'a, T> {
+  first: PhaseCursor<'a, T>,
+  middle: PhaseCursor<'a, T>,
+  last: PhaseCursor<'a, T>,
+}
+
+impl<'a, T> SecondDifference<'a, T>
+  where T: Scalar
+{
+  fn new(data: ClockData<'a, T>, tau0: f64, m: usize, index: usize) -> Self {
+    SecondDifference {
+      first: PhaseCursor::new(data, tau0, index),
+      middle: PhaseCursor::new(data, tau0, index + m),
+      last: PhaseCursor::new(data, tau0, index + 2 * m),
+    }
+  }
+
+  fn value(&self) -> f64 {
+    self.last.value - 2.0 * self.middle.value + self.first.value
+  }
+
+  fn advance(&mut self, steps: usize) {
+    self.first.advance(steps);
+    self.middle.advance(steps);
+    self.last.advance(steps);
+  }
+}
+
+/// Check the averaging factor `m` against the series length,
+/// which must hold at least `spans * m + 1` phase points
+fn check_length<T: Scalar>(data: &ClockData<'_, T>, m: usize, spans: usize) -> Result<usize, KalmanError> {
+  let len = data.phase_len();
+  if m == 0 || len < spans * m + 1 {
+    return Err(KalmanError::InsufficientData);
+  }
+  Ok(len)
+}
+
+/// Sum `count` squared second differences from index 0, stepping by `stride`
+fn sum_second_differences<T: Scalar>(data: ClockData<'_, T>, tau0: f64, m: usize, stride: usize, count: usize)
+  -> f64
+{
+  let mut diff = SecondDifference::new(data, tau0, m, 0);
+  let mut sum = 0.0;
+  for k in 0..count {
+    sum += diff.value() * diff.value();
+    if k + 1 < count {
+      diff.advance(stride);
+    }
+  }
+  sum
+}
+
+/// Allan deviation at averaging time `tau = m * tau0`, using
+/// non-overlapping samples of a series recorded at intervals of `tau0` seconds
+pub fn allan_deviation<T>(data: ClockData<'_, T>, tau0: f64, m: usize) -> Result<f64, KalmanError>
+  where T: Scalar
+{
+  let len = check_length(&data, m, 2)?;
+  let tau0 = tau0.abs();
+  let tau = m as f64 * tau0;
+  let count = (len - 1 - 2 * m) / m + 1;
+  let sum = sum_second_differences(data, tau0, m, m, count);
+  Ok(num_traits::Float::sqrt(sum / (2.0 * count as f64 * tau * tau)))
+}
+
+/// Overlapping Allan deviation at averaging time `tau = m * tau0`,
+/// which uses every sample and gives tighter confidence than `allan_deviation`
+pub fn overlapping_allan_deviation<T>(data: ClockData<'_, T>, tau0: f64, m: usize) -> Result<f64, KalmanError>
+  where T: Scalar
+{
+  let len = check_length(&data, m, 2)?;
+  let tau0 = tau0.abs();
+  let tau = m as f64 * tau0;
+  let count = len - 2 * m;
+  let sum = sum_second_differences(data, tau0, m, 1, count);
+  Ok(num_traits::Float::sqrt(sum / (2.0 * count as f64 * tau * tau)))
+}
+
+/// Modified Allan deviation at averaging time `tau = m * tau0`,
+/// which additionally averages the phase and so distinguishes
+/// white from flicker phase noise
+pub fn modified_allan_deviation<T>(data: ClockData<'_, T>, tau0: f64, m: usize) -> Result<f64, KalmanError>
+  where T: Scalar
+{
+  let len = check_length(&data, m, 3)?;
+  let tau0 = tau0.abs();
+  let tau = m as f64 * tau0;
+  let count = len - 3 * m + 1;
+
+  // sliding window of m second differences: `trail` leaves, `lead` enters
+  let mut trail = SecondDifference::new(data, tau0, m, 0);
+  let mut lead = SecondDifference::new(data, tau0, m, 0);
+  let mut window = 0.0;
+  for i in 0..m {
+    window += lead.value();
+    if i + 1 < m {
+      lead.advance(1);
+    }
+  }
+  let mut sum = 0.0;
+  for j in 0..count {
+    sum += window * window;
+    if j + 1 < count {
+      lead.advance(1);
+      window += lead.value() - trail.value();
+      trail.advance(1);
+    }
+  }
+  let m = m as f64;
+  Ok(num_traits::Float::sqrt(sum / (2.0 * m * m * count as f64 * tau * tau)))
+}
+
+/// Power-law clock noise coefficients, in the parameterization used by
+/// three-state (phase, frequency, drift) clock models:
+///   AVAR(tau) = 3 white_phase / tau^2 + q1 / tau + q2 tau / 3 + q3 tau^3 / 20
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PowerLawNoise {
+  /// White phase noise variance (s^2): the measurement variance of phase readings
+  pub white_phase: f64,
+  /// White frequency noise (random walk phase) diffusion coefficient (s^2 / s)
+  pub q1: f64,
+  /// Random walk frequency noise diffusion coefficient (1 / s)
+  pub q2: f64,
+  /// Random run (random walk drift) noise diffusion coefficient (1 / s^3)
+  pub q3: f64,
+}
+
+impl PowerLawNoise {
+  pub fn new(white_phase: f64, q1: f64, q2: f64, q3: f64) -> Self {
+    PowerLawNoise { white_phase, q1, q2, q3 }
+  }
+
+  /// The basis terms of the Allan variance model at averaging time `tau`
+  fn basis(tau: f64) -> [f64; 4] {
+    [3.0 / (tau * tau), 1.0 / tau, tau / 3.0, tau * tau * tau / 20.0]
+  }
+
+  fn coefficients(&self) -> [f64; 4] {
+    [self.white_phase, self.q1, self.q2, self.q3]
+  }
+
+  /// Allan variance predicted by this noise model at averaging time `tau`
+  pub fn allan_variance(&self, tau: f64) -> f64 {
+    Self::basis(tau).iter().zip(self.coefficients()).map(|(b, c)| b * c).sum()
+  }
+
+  /// Allan deviation predicted by this noise model at averaging time `tau`
+  pub fn allan_deviation(&self, tau: f64) -> f64 {
+    num_traits::Float::sqrt(self.allan_variance(tau))
+  }
+
+  /// Growth in phase variance over an interval `dt` with no observation:
+  /// the phase term of the clock model process noise,
+  ///   q1 dt + q2 dt^3 / 3 + q3 dt^5 / 20
+  pub fn phase_process_variance(&self, dt: f64) -> f64 {
+    let dt2 = dt * dt;
+    self.q1 * dt + self.q2 * dt * dt2 / 3.0 + self.q3 * dt * dt2 * dt2 / 20.0
+  }
+
+  /// A single-variable phase (time offset) filter tuned from this noise model,
+  /// for use with one `predict` call every `dt` seconds.
+  /// Very small variances may round to zero in narrow fixed-point types.
+  pub fn to_kalman<T: Scalar>(&self, estimate: T, uncertainty: T, dt: f64) -> KalmanState<T> {
+    KalmanState::new(
+      estimate,
+      uncertainty,
+      T::from_f64(self.white_phase),
+      T::from_f64(self.phase_process_variance(dt)),
+    )
+  }
+}
+
+/// Solve the `size` x `size` system `a x = b` by Gaussian elimination
+fn solve(mut a: [[f64; 4]; 4], mut b: [f64; 4], size: usize) -> Option<[f64; 4]> {
+  for col in 0..size {
+    let pivot = (col..size).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+    if a[pivot][col].abs() < 1E-12 {
+      return None;
+    }
+    a.swap(col, pivot);
+    b.swap(col, pivot);
+    for row in col + 1..size {
+      let factor = a[row][col] / a[col][col];
+      let pivot_row = a[col];
+      for (target, source) in a[row][col..size].iter_mut().zip(&pivot_row[col..size]) {
+        *target -= factor * source;
+      }
+      b[row] -= factor * b[col];
+    }
+  }
+  let mut x = [0.0; 4];
+  for row in (0..size).rev() {
+    let tail: f64 = (row + 1..size).map(|k| a[row][k] * x[k]).sum();
+    x[row] = (b[row] - tail) / a[row][row];
+  }
+  Some(x)
+}
+
+/// Fit non-negative power-law noise coefficients to measured
+/// `(tau, allan_deviation)` points, such as those from `overlapping_allan_deviation`.
+/// The fit minimizes the relative error in Allan variance, trying every subset
+/// of the four noise terms and keeping the best fit with no negative coefficient.
+/// Points with a non-positive `tau` or deviation are ignored.
+pub fn fit_power_law(points: &[(f64, f64)]) -> Result<PowerLawNoise, KalmanError> {
+  let valid = || points.iter().filter(|(tau, dev)| *tau > 0.0 && *dev > 0.0);
+  if valid().next().is_none() {
+    return Err(KalmanError::InsufficientData);
+  }
+
+  // scale each basis column so the normal equations are well conditioned
+  let mut scale = [0.0f64; 4];
+  for &(tau, dev) in valid() {
+    for (s, b) in scale.iter_mut().zip(PowerLawNoise::basis(tau)) {
+      *s = s.max(b / (dev * dev));
+    }
+  }
+
+  let mut best: Option<([f64; 4], f64)> = None;
+  for mask in 1u32..16 {
+    let mut active = [0usize; 4];
+    let mut size = 0;
+    for term in (0..4).filter(|term| mask & (1 << term) != 0) {
+      active[size] = term;
+      size += 1;
+    }
+
+    // normal equations for the relative residual (model - avar) / avar
+    let mut a = [[0.0; 4]; 4];
+    let mut b = [0.0; 4];
+    for &(tau, dev) in valid() {
+      let basis = PowerLawNoise::basis(tau);
+      let avar = dev * dev;
+      for i in 0..size {
+        let ri = basis[active[i]] / (avar * scale[active[i]]);
+        b[i] += ri;
+        for j in 0..size {
+          a[i][j] += ri * basis[active[j]] / (avar * scale[active[j]]);
+        }
+      }
+    }
+    let Some(x) = solve(a, b, size) else { continue };
+    if x[..size].iter().any(|&c| c < 0.0) {
+      continue;
+    }
+    let mut coefficients = [0.0; 4];
+    for i in 0..size {
+      coefficients[active[i]] = x[i] / scale[active[i]];
+    }
+    let model = PowerLawNoise::new(coefficients[0], coefficients[1], coefficients[2], coefficients[3]);
+    let residual: f64 = valid()
+      .map(|&(tau, dev)| {
+        let error = model.allan_variance(tau) / (dev * dev) - 1.0;
+        error * error
+      })
+      .sum();
+    if best.is_none_or(|(_, r)| residual < r) {
+      best = Some((coefficients, residual));
+    }
+  }
+  let (c, _) = best.ok_or(KalmanError::SingularMatrix)?;
+  Ok(PowerLawNoise::new(c[0], c[1], c[2], c[3]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+  use rand_distr::{Distribution, Normal};
+
+  const COUNT: usize = 20000;
+
+  /// Phase of a clock with white frequency noise of diffusion `q1`, sampled every `tau0`
+  fn white_fm_phase(q1: f64, tau0: f64, seed: u64) -> [f64; COUNT] {
+    let step = Normal::new(0.0f64, (q1 * tau0).sqrt()).unwrap();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut phase = [0.0; COUNT];
+    for i in 1..COUNT {
+      phase[i] = phase[i - 1] + step.sample(&mut rng);
+    }
+    phase
+  }
+
+  #[test]
+  fn test_white_fm_deviations_f64() {
+    let q1 = 1E-18;
+    let phase = white_fm_phase(q1, 1.0, 24);
+    for m in [1, 4, 16, 64] {
+      let tau = m as f64;
+      let expected = (q1 / tau).sqrt();
+      let adev = allan_deviation(ClockData::Phase(&phase), 1.0, m).unwrap();
+      let oadev = overlapping_allan_deviation(ClockData::Phase(&phase), 1.0, m).unwrap();
+      let mdev = modified_allan_deviation(ClockData::Phase(&phase), 1.0, m).unwrap();
+      println!("tau: {} adev: {:e} oadev: {:e} mdev: {:e}", tau, adev, oadev, mdev);
+      assert!((oadev / expected - 1.0).abs() < 0.15);
+      assert!((adev / expected - 1.0).abs() < 0.3);
+      if m == 1 {
+        // with no averaging, the modified deviation is the overlapping one
+        assert!((mdev / oadev - 1.0).abs() < 1E-12);
+      }
+      else {
+        // for white FM, MVAR / AVAR approaches 1/2
+        assert!(((mdev * mdev) / (oadev * oadev) - 0.5).abs() < 0.1);
+      }
+    }
+  }
+
+  #[test]
+  fn test_frequency_matches_phase_f64() {
+    let phase = white_fm_phase(1E-2, 0.5, 25);
+    let mut frequency = [0.0; COUNT - 1];
+    for (i, y) in frequency.iter_mut().enumerate() {
+      *y = (phase[i + 1] - phase[i]) / 0.5;
+    }
+    for m in [1, 3, 10] {
+      let from_phase = modified_allan_deviation(ClockData::Phase(&phase), 0.5, m).unwrap();
+      let from_frequency = modified_allan_deviation(ClockData::Frequency(&frequency), 0.5, m).unwrap();
+      assert!((from_phase / from_frequency - 1.0).abs() < 1E-6);
+      let from_phase = allan_deviation(ClockData::Phase(&phase), 0.5, m).unwrap();
+      let from_frequency = allan_deviation(ClockData::Frequency(&frequency), 0.5, m).unwrap();
+      assert!((from_phase / from_frequency - 1.0).abs() < 1E-6);
+    }
+    assert_eq!(
+      overlapping_allan_deviation(ClockData::Phase(&phase[..4]), 1.0, 2),
+      Err(KalmanError::InsufficientData));
+  }
+
+  #[test]
+  fn test_fit_recovers_model() {
+    let truth = PowerLawNoise::new(1E-18, 1E-20, 1E-26, 0.0);
+    let mut points = [(0.0, 0.0); 12];
+    for (i, point) in points.iter_mut().enumerate() {
+      let tau = 10f64.powi(i as i32 - 2);
+      *point = (tau, truth.allan_deviation(tau));
+    }
+    let fitted = fit_power_law(&points).unwrap();
+    println!("fitted: {:?}", fitted);
+    assert!((fitted.white_phase / truth.white_phase - 1.0).abs() < 1E-6);
+    assert!((fitted.q1 / truth.q1 - 1.0).abs() < 1E-6);
+    assert!((fitted.q2 / truth.q2 - 1.0).abs() < 1E-6);
+    assert!(fitted.q3 < 1E-40);
+  }
+
+  #[test]
+  fn test_tune_from_data_i32f32() {
+    // a noisy clock measured in units where the fixed-point type has room
+    type TestType = I32F32;
+    let q1 = 1E-3;
+    let phase = white_fm_phase(q1, 1.0, 26).map(TestType::from_num);
+    let mut points = [(0.0, 0.0); 5];
+    for (i, point) in points.iter_mut().enumerate() {
+      let m = 1 << (2 * i);
+      *point = (m as f64, overlapping_allan_deviation(ClockData::Phase(&phase), 1.0, m).unwrap());
+    }
+    let fitted = fit_power_law(&points).unwrap();
+    println!("fitted: {:?}", fitted);
+    assert!((fitted.q1 / q1 - 1.0).abs() < 0.2);
+
+    let kstate = fitted.to_kalman(phase[COUNT - 1], TestType::from_num(1), 1.0);
+    assert!((kstate.process_variance().to_num::<f64>() - q1).abs() < 3E-4);
+  }
+}
@@ -0,0 +1,173 @@
+use crate::scalar::{blend, signed_diff};
+use crate::{KalmanState, Scalar, UpdateReport};
+
+impl<T> KalmanState<T>
+  where T: Scalar
+{
+  /// Update step incorporating a set of simultaneous `(observation, variance)`
+  /// readings as one information-weighted update.
+  /// Unlike a sequence of single updates, the result does not depend on
+  /// the order of the readings. Returns `None` (leaving the state unchanged)
+  /// if there are no readings.
+  pub fn update_batch(&mut self, readings: &[(T, T)]) -> Option<UpdateReport<T>> {
+    let (observation, variance) = combine_readings(readings)?;
+    if variance == T::zero() {
+      // exact readings: take the observation outright (gain of one), which
+      // avoids dividing zero by zero when the prior uncertainty is also zero
+      let report = UpdateReport {
+        innovation: signed_diff(observation, self.estimate),
+        innovation_variance: self.uncertainty,
+        gain: T::one(),
+        residual: T::zero(),
+      };
+      self.estimate = observation;
+      self.uncertainty = T::zero();
+      return Some(report);
+    }
+    Some(self.update_with_variance(observation, variance))
+  }
+}
+
+/// Combine simultaneous readings into a single equivalent reading.
+/// The combined observation is the inverse-variance weighted mean, and the
+/// combined variance is the inverse of the summed information.
+/// Weights are taken relative to the smallest variance, so that the
+/// information (1 / variance) never needs to be represented directly;
+/// this matters for narrow fixed-point types and tiny variances.
+fn combine_readings<T>(readings: &[(T, T)]) -> Option<(T, T)>
+  where T: Scalar
+{
+  let min_variance = readings.iter()
+    .map(|&(_, variance)| variance.abs())
+    .reduce(|a, b| if b < a { b } else { a })?;
+
+  let mut mean = readings[0].0;
+  let mut total_weight = T::zero();
+  for &(observation, variance) in readings {
+    let variance = variance.abs();
+    let weight =
+      if min_variance == T::zero() {
+        // exact readings override all others
+        if variance == T::zero() { T::one() } else { T::zero() }
+      }
+      else {
+        min_variance / variance
+      };
+    if weight == T::zero() {
+      continue;
+    }
+    // running weighted mean, safe for unsigned types
+    total_weight = total_weight + weight;
+    mean = blend(mean, observation, weight / total_weight);
+  }
+
+  Some((mean, min_variance / total_weight))
+}
+
+/// Kalman fusion function (fold function) for a batch of simultaneous
+/// `(observation, variance)` readings from multiple sensors.
+/// The readings are incorporated as one information-weighted update,
+/// and the process variance is applied once for the whole batch.
+pub fn fuse_batch<T>(state: &KalmanState<T>, readings: &[(T, T)]) -> KalmanState<T>
+  where
+    T: Scalar,
+{
+  let mut new_state = *state;
+  if new_state.update_batch(readings).is_none() {
+    return new_state;
+  }
+
+  // adjust for process variance (normally done in a "predict" step),
+  // in proportion to how far the estimate moved
+  let est_diff = state.estimate.abs_diff(new_state.estimate);
+  new_state.uncertainty = new_state.uncertainty + state.process_variance * est_diff;
+  new_state
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::{I16F16, I32F32, U32F32};
+
+  #[test]
+  fn test_batch_matches_sequential_f64() {
+    let readings = [(10.2f64, 0.5), (9.7, 0.1), (10.9, 2.0), (10.0, 0.25)];
+    let start = KalmanState::new(8.0f64, 1.0, 1.0, 0.0);
+
+    let mut batch = start;
+    let report = batch.update_batch(&readings).unwrap();
+    let mut sequential = start;
+    for &(observation, variance) in readings.iter() {
+      sequential.update_with_variance(observation, variance);
+    }
+    println!("batch: {} sequential: {} gain: {}", batch.estimate, sequential.estimate, report.gain);
+    assert!((batch.estimate - sequential.estimate).abs() < 1E-12);
+    assert!((batch.uncertainty - sequential.uncertainty).abs() < 1E-12);
+
+    // order of readings does not matter
+    let mut reversed = [(0.0f64, 0.0); 4];
+    for (i, reading) in readings.iter().rev().enumerate() {
+      reversed[i] = *reading;
+    }
+    let forward = fuse_batch(&start, &readings);
+    let backward = fuse_batch(&start, &reversed);
+    assert!((forward.estimate - backward.estimate).abs() < 1E-12);
+
+    let mut empty = start;
+    assert!(empty.update_batch(&[]).is_none());
+    assert_eq!(empty.estimate, start.estimate);
+  }
+
+  #[test]
+  fn test_batch_tiny_variance_i16f16() {
+    type TestType = I16F16;
+    // 1 / 1E-4 does not fit in I16F16, but relative weights do
+    let readings = [
+      (TestType::from_num(100), TestType::from_num(1E-4)),
+      (TestType::from_num(101), TestType::from_num(1E-4)),
+    ];
+    let mut kstate = KalmanState::new(
+      TestType::from_num(0),
+      TestType::from_num(100),
+      TestType::from_num(1),
+      TestType::from_num(0),
+    );
+    kstate.update_batch(&readings);
+    println!("est: {} uncert: {}", kstate.estimate, kstate.uncertainty);
+    assert!((kstate.estimate - TestType::from_num(100.5)).abs() < TestType::from_num(1E-2));
+  }
+
+  #[test]
+  fn test_batch_unsigned_u32f32() {
+    type TestType = U32F32;
+    let readings = [
+      (TestType::from_num(1000), TestType::from_num(1)),
+      (TestType::from_num(990), TestType::from_num(1)),
+      (TestType::from_num(1010), TestType::from_num(2)),
+    ];
+    let kstate = KalmanState::new(
+      TestType::from_num(1000),
+      TestType::from_num(1E6),
+      TestType::from_num(1),
+      TestType::from_num(0),
+    );
+    let fused = fuse_batch(&kstate, &readings);
+    // inverse-variance weighted mean: (1000 + 990 + 505) / 2.5
+    assert!(fused.estimate.abs_diff(TestType::from_num(998)) < TestType::from_num(1E-2));
+  }
+
+  #[test]
+  fn test_exact_reading_on_certain_state_i32f32() {
+    type TestType = I32F32;
+    let start = KalmanState::new(
+      TestType::from_num(3),
+      TestType::from_num(0),
+      TestType::from_num(1),
+      TestType::from_num(0),
+    );
+    let readings = [(TestType::from_num(5), TestType::from_num(0)), (TestType::from_num(4), TestType::from_num(1))];
+    let fused = fuse_batch(&start, &readings);
+    assert_eq!(fused.estimate, TestType::from_num(5));
+    assert_eq!(fused.uncertainty, TestType::from_num(0));
+  }
+}
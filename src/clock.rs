@@ -0,0 +1,311 @@
+use num_traits::Signed;
+
+use crate::kalman_filter::correct;
+use crate::{KalmanError, Matrix, PowerLawNoise, Scalar, Vector};
+
+/// Clock process noise diffusion coefficients, in the same parameterization
+/// as `PowerLawNoise`:
+///  - `q1` drives a random walk in phase (white frequency noise)
+///  - `q2` drives a random walk in frequency
+///  - `q3` drives a random walk in frequency drift
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "ClockNoiseData<T>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct ClockNoise<T> {
+  pub q1: T,
+  pub q2: T,
+  pub q3: T,
+}
+
+/// Unvalidated `ClockNoise` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct ClockNoiseData<T> {
+  q1: T,
+  q2: T,
+  q3: T,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<ClockNoiseData<T>> for ClockNoise<T>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: ClockNoiseData<T>) -> Result<Self, Self::Error> {
+    let zero = T::zero();
+    if data.q1 < zero || data.q2 < zero || data.q3 < zero {
+      return Err("negative noise coefficient");
+    }
+    Ok(ClockNoise { q1: data.q1, q2: data.q2, q3: data.q3 })
+  }
+}
+
+impl<T> ClockNoise<T>
+  where T: Scalar
+{
+  pub fn new(q1: T, q2: T, q3: T) -> Self {
+    ClockNoise { q1: q1.abs(), q2: q2.abs(), q3: q3.abs() }
+  }
+
+  /// Take the coefficients from a noise model fitted to Allan deviation data.
+  /// Very small coefficients may round to zero in fixed-point types;
+  /// scaling the time units (for example to nanoseconds) avoids this.
+  pub fn from_power_law(noise: &PowerLawNoise) -> Self {
+    ClockNoise::new(T::from_f64(noise.q1), T::from_f64(noise.q2), T::from_f64(noise.q3))
+  }
+
+  /// The process noise covariance accumulated over an interval `dt`
+  pub fn process_noise(&self, dt: T) -> Matrix<T, 3, 3> {
+    let two = T::one() + T::one();
+    let three = two + T::one();
+    let six = three + three;
+    let eight = six + two;
+    let twenty = eight + eight + two + two;
+    let dt2 = dt * dt;
+    let dt3 = dt2 * dt;
+    let (q1, q2, q3) = (self.q1, self.q2, self.q3);
+
+    let q00 = q1 * dt + q2 * dt3 / three + q3 * dt3 * dt2 / twenty;
+    let q01 = q2 * dt2 / two + q3 * dt2 * dt2 / eight;
+    let q02 = q3 * dt3 / six;
+    let q11 = q2 * dt + q3 * dt3 / three;
+    let q12 = q3 * dt2 / two;
+    let q22 = q3 * dt;
+    Matrix::new([
+      [q00, q01, q02],
+      [q01, q11, q12],
+      [q02, q12, q22],
+    ])
+  }
+}
+
+/// A three-state clock model tracking phase (time) offset, fractional
+/// frequency offset and frequency drift, observed through phase measurements.
+/// Unlike the random-walk `KalmanState`, this follows a crystal oscillator's
+/// steadily changing rate without lag.
+/// Requires a signed Scalar type, since the covariance may be negative;
+/// fixed-point users will want a wide type such as `I64F64`.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "ClockStateData<T>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct ClockState<T> {
+  /// Estimated phase (time) offset
+  pub phase: T,
+  /// Estimated fractional frequency offset (phase change per unit of time)
+  pub frequency: T,
+  /// Estimated frequency drift (frequency change per unit of time)
+  pub drift: T,
+  /// Covariance of the (phase, frequency, drift) estimate
+  pub covariance: Matrix<T, 3, 3>,
+  measurement_variance: T,  // Uncertainty in each phase measurement
+  noise: ClockNoise<T>,
+}
+
+/// Unvalidated `ClockState` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(deserialize = "T: Scalar + serde::Deserialize<'de>"))]
+struct ClockStateData<T> {
+  phase: T,
+  frequency: T,
+  drift: T,
+  covariance: Matrix<T, 3, 3>,
+  measurement_variance: T,
+  noise: ClockNoise<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<ClockStateData<T>> for ClockState<T>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: ClockStateData<T>) -> Result<Self, Self::Error> {
+    let zero = T::zero();
+    if (0..3).any(|i| data.covariance[(i, i)] < zero) || data.measurement_variance < zero {
+      return Err("negative uncertainty or variance");
+    }
+    Ok(ClockState {
+      phase: data.phase,
+      frequency: data.frequency,
+      drift: data.drift,
+      covariance: data.covariance,
+      measurement_variance: data.measurement_variance,
+      noise: data.noise,
+    })
+  }
+}
+
+impl<T> ClockState<T>
+  where T: Scalar + Signed
+{
+  pub fn new(
+    phase: T,
+    frequency: T,
+    drift: T,
+    covariance: Matrix<T, 3, 3>,
+    measurement_variance: T,
+    noise: ClockNoise<T>) -> ClockState<T>
+  {
+    ClockState {
+      phase,
+      frequency,
+      drift,
+      covariance,
+      measurement_variance: Scalar::abs(measurement_variance),
+      noise,
+    }
+  }
+
+  /// Uncertainty (variance) of the phase estimate
+  pub fn phase_uncertainty(&self) -> T {
+    self.covariance[(0, 0)]
+  }
+
+  pub fn noise(&self) -> &ClockNoise<T> {
+    &self.noise
+  }
+
+  /// Predict step:
+  /// propagate the clock forward by `dt` at the estimated frequency and drift,
+  /// and grow the covariance by the integrated process noise.
+  pub fn predict(&mut self, dt: T) {
+    let dt = Scalar::abs(dt);
+    let half_dt2 = dt * dt / (T::one() + T::one());
+    let transition = Matrix::new([
+      [T::one(), dt, half_dt2],
+      [T::zero(), T::one(), dt],
+      [T::zero(), T::zero(), T::one()],
+    ]);
+
+    self.phase = self.phase + self.frequency * dt + self.drift * half_dt2;
+    self.frequency = self.frequency + self.drift * dt;
+    self.covariance = transition * self.covariance * transition.transpose() + self.noise.process_noise(dt);
+  }
+
+  /// Update step:
+  /// incorporate a single phase measurement.
+  /// Fails if the innovation variance is zero, in which case the state is left unchanged.
+  pub fn update(&mut self, observation: T) -> Result<(), KalmanError> {
+    self.update_with_variance(observation, self.measurement_variance)
+  }
+
+  /// Update step, using the given measurement variance
+  /// for this observation instead of the one configured at construction.
+  pub fn update_with_variance(&mut self, observation: T, measurement_variance: T) -> Result<(), KalmanError> {
+    let mut state: Vector<T, 3> = Matrix::new([[self.phase], [self.frequency], [self.drift]]);
+    let mut covariance = self.covariance;
+    let innovation = Matrix::new([[observation - self.phase]]);
+    let h = Matrix::new([[T::one(), T::zero(), T::zero()]]);
+    let r = Matrix::new([[Scalar::abs(measurement_variance)]]);
+    correct(&mut state, &mut covariance, &innovation, &h, &r)?;
+
+    self.phase = state[(0, 0)];
+    self.frequency = state[(1, 0)];
+    self.drift = state[(2, 0)];
+    self.covariance = covariance;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I64F64;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+  use rand_distr::{Distribution, Normal};
+
+  #[test]
+  fn test_tracks_drifting_oscillator_f64() {
+    // phase in seconds: 200 ppb frequency offset drifting by 1E-11 per second
+    let (frequency, drift) = (2E-7f64, 1E-11);
+    let noise = Normal::new(0.0f64, 1E-8).unwrap();
+    let mut rng = StdRng::seed_from_u64(25);
+    let mut clock = ClockState::new(
+      0.0f64,
+      0.0,
+      0.0,
+      Matrix::new([[1E-12, 0.0, 0.0], [0.0, 1E-12, 0.0], [0.0, 0.0, 1E-18]]),
+      1E-16,
+      ClockNoise::new(1E-22, 1E-28, 1E-36),
+    );
+    for i in 1..=2000 {
+      let t = i as f64;
+      clock.predict(1.0);
+      clock.update(frequency * t + drift * t * t / 2.0 + noise.sample(&mut rng)).unwrap();
+    }
+    let t = 2000.0;
+    println!("phase: {:e} frequency: {:e} drift: {:e}", clock.phase, clock.frequency, clock.drift);
+    assert!((clock.phase - (frequency * t + drift * t * t / 2.0)).abs() < 1E-8);
+    assert!((clock.frequency - (frequency + drift * t)).abs() < 1E-10);
+    assert!((clock.drift - drift).abs() < 1E-12);
+  }
+
+  #[test]
+  fn test_process_noise_matches_power_law() {
+    let fitted = PowerLawNoise::new(1E-18, 1E-20, 1E-26, 1E-34);
+    let noise: ClockNoise<f64> = ClockNoise::from_power_law(&fitted);
+    let dt = 10.0;
+    let q = noise.process_noise(dt);
+    assert!((q[(0, 0)] / fitted.phase_process_variance(dt) - 1.0).abs() < 1E-12);
+    assert_eq!(q[(0, 1)], q[(1, 0)]);
+    assert_eq!(q[(2, 2)], 1E-34 * dt);
+  }
+
+  #[test]
+  fn test_tracks_drifting_oscillator_i64f64() {
+    // phase in nanoseconds, so the variances are comfortably representable
+    type TestType = I64F64;
+    let (frequency, drift) = (200.0f64, 0.01);
+    let mut clock = ClockState::new(
+      TestType::from_num(0),
+      TestType::from_num(0),
+      TestType::from_num(0),
+      Matrix::new([
+        [TestType::from_num(1E6), TestType::from_num(0), TestType::from_num(0)],
+        [TestType::from_num(0), TestType::from_num(1E6), TestType::from_num(0)],
+        [TestType::from_num(0), TestType::from_num(0), TestType::from_num(1)],
+      ]),
+      TestType::from_num(100),
+      ClockNoise::new(TestType::from_num(1E-2), TestType::from_num(1E-8), TestType::from_num(1E-16)),
+    );
+    let dt = TestType::from_num(1);
+    for i in 1..=2000 {
+      let t = i as f64;
+      // alternating +/- 10 ns of measurement noise
+      let noise = if i % 2 == 0 { 10.0 } else { -10.0 };
+      clock.predict(dt);
+      clock.update(TestType::from_num(frequency * t + drift * t * t / 2.0 + noise)).unwrap();
+    }
+    let t = 2000.0;
+    println!("phase: {} frequency: {} drift: {}", clock.phase, clock.frequency, clock.drift);
+    assert!((clock.phase.to_num::<f64>() - (frequency * t + drift * t * t / 2.0)).abs() < 5.0);
+    assert!((clock.frequency.to_num::<f64>() - (frequency + drift * t)).abs() < 0.05);
+    assert!((clock.drift.to_num::<f64>() - drift).abs() < 1E-4);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_rejects_negative_variance() {
+    let clock = ClockState::new(
+      0.0f64,
+      0.0,
+      0.0,
+      Matrix::new([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+      0.5,
+      ClockNoise::new(0.25, 0.125, 0.0625),
+    );
+    let json = serde_json::to_string(&clock).unwrap();
+    assert!(serde_json::from_str::<ClockState<f64>>(&json).is_ok());
+    let corrupt = json.replace(r#""measurement_variance":0.5"#, r#""measurement_variance":-0.5"#);
+    assert!(serde_json::from_str::<ClockState<f64>>(&corrupt).is_err());
+    let corrupt = json.replace(r#""q2":0.125"#, r#""q2":-0.125"#);
+    assert!(serde_json::from_str::<ClockState<f64>>(&corrupt).is_err());
+  }
+}
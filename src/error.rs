@@ -0,0 +1,34 @@
+use core::fmt;
+
+/// Errors reported by the filters in this crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KalmanError {
+  /// A matrix that must be inverted (such as the innovation covariance) is singular
+  SingularMatrix,
+  /// A covariance matrix that must be factored is not positive semi-definite
+  NotPositiveDefinite,
+  /// An arithmetic operation overflowed the range of the numeric type
+  Overflow,
+  /// An output buffer is too small to hold the results
+  BufferTooSmall,
+  /// A measurement is older than the history retained by the filter
+  MeasurementTooOld,
+  /// Too few observations were supplied to estimate the requested quantity
+  InsufficientData,
+}
+
+impl fmt::Display for KalmanError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      KalmanError::SingularMatrix => write!(f, "singular matrix"),
+      KalmanError::NotPositiveDefinite => write!(f, "matrix not positive semi-definite"),
+      KalmanError::Overflow => write!(f, "arithmetic overflow"),
+      KalmanError::BufferTooSmall => write!(f, "output buffer too small"),
+      KalmanError::MeasurementTooOld => write!(f, "measurement older than retained history"),
+      KalmanError::InsufficientData => write!(f, "insufficient data"),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for KalmanError {}
@@ -0,0 +1,181 @@
+use num_traits::Signed;
+
+use crate::kalman_filter::correct;
+use crate::{KalmanError, Matrix, Scalar, Vector};
+
+/// A nonlinear process and observation model for an `ExtendedKalmanFilter`,
+/// with `N` state variables and `M` measured variables.
+/// The Jacobians are evaluated at the current state estimate.
+pub trait NonlinearModel<T, const N: usize, const M: usize> {
+  /// State transition function f(x)
+  fn transition(&self, state: &Vector<T, N>) -> Vector<T, N>;
+
+  /// Jacobian of the state transition, df/dx
+  fn transition_jacobian(&self, state: &Vector<T, N>) -> Matrix<T, N, N>;
+
+  /// Observation function h(x)
+  fn observation(&self, state: &Vector<T, N>) -> Vector<T, M>;
+
+  /// Jacobian of the observation function, dh/dx
+  fn observation_jacobian(&self, state: &Vector<T, N>) -> Matrix<T, M, N>;
+}
+
+/// An extended Kalman filter with `N` state variables and `M` measured variables,
+/// linearizing user-supplied nonlinear models about the current estimate.
+/// The models may be supplied as closures per step, or as a `NonlinearModel`.
+/// Requires a signed Scalar type.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtendedKalmanFilter<T, const N: usize, const M: usize> {
+  /// Estimated state x
+  pub state: Vector<T, N>,
+  /// Estimated state covariance P
+  pub covariance: Matrix<T, N, N>,
+  /// Process noise covariance Q
+  pub process_noise: Matrix<T, N, N>,
+  /// Measurement noise covariance R
+  pub measurement_noise: Matrix<T, M, M>,
+}
+
+impl<T, const N: usize, const M: usize> ExtendedKalmanFilter<T, N, M>
+  where T: Scalar + Signed
+{
+  pub fn new(
+    state: Vector<T, N>,
+    covariance: Matrix<T, N, N>,
+    process_noise: Matrix<T, N, N>,
+    measurement_noise: Matrix<T, M, M>) -> Self
+  {
+    ExtendedKalmanFilter {
+      state,
+      covariance,
+      process_noise,
+      measurement_noise,
+    }
+  }
+
+  /// Predict step with the transition function `f` and its Jacobian `f_jacobian`:
+  /// x = f(x), P = F P F' + Q
+  pub fn predict_with<F, J>(&mut self, f: F, f_jacobian: J)
+    where
+      F: Fn(&Vector<T, N>) -> Vector<T, N>,
+      J: Fn(&Vector<T, N>) -> Matrix<T, N, N>,
+  {
+    let jacobian = f_jacobian(&self.state);
+    self.state = f(&self.state);
+    self.covariance = jacobian * self.covariance * jacobian.transpose() + self.process_noise;
+  }
+
+  /// Update step with the observation function `h` and its Jacobian `h_jacobian`.
+  /// Fails if the innovation covariance is singular,
+  /// in which case the filter is left unchanged.
+  pub fn update_with<H, J>(&mut self, measurement: &Vector<T, M>, h: H, h_jacobian: J)
+    -> Result<(), KalmanError>
+    where
+      H: Fn(&Vector<T, N>) -> Vector<T, M>,
+      J: Fn(&Vector<T, N>) -> Matrix<T, M, N>,
+  {
+    let jacobian = h_jacobian(&self.state);
+    let innovation = *measurement - h(&self.state);
+    correct(&mut self.state, &mut self.covariance, &innovation, &jacobian, &self.measurement_noise)
+  }
+
+  /// Predict step using the transition functions of `model`
+  pub fn predict<Model>(&mut self, model: &Model)
+    where Model: NonlinearModel<T, N, M>
+  {
+    self.predict_with(|x| model.transition(x), |x| model.transition_jacobian(x));
+  }
+
+  /// Update step using the observation functions of `model`
+  pub fn update<Model>(&mut self, model: &Model, measurement: &Vector<T, M>) -> Result<(), KalmanError>
+    where Model: NonlinearModel<T, N, M>
+  {
+    self.update_with(measurement, |x| model.observation(x), |x| model.observation_jacobian(x))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+
+  /// A stationary point observed by range from two beacons
+  struct RangeModel {
+    beacons: [(f64, f64); 2],
+  }
+
+  impl NonlinearModel<f64, 2, 2> for RangeModel {
+    fn transition(&self, state: &Vector<f64, 2>) -> Vector<f64, 2> {
+      *state
+    }
+
+    fn transition_jacobian(&self, _state: &Vector<f64, 2>) -> Matrix<f64, 2, 2> {
+      Matrix::identity()
+    }
+
+    fn observation(&self, state: &Vector<f64, 2>) -> Vector<f64, 2> {
+      let mut out = Matrix::zeros();
+      for (i, &(bx, by)) in self.beacons.iter().enumerate() {
+        out[(i, 0)] = ((state[(0, 0)] - bx).powi(2) + (state[(1, 0)] - by).powi(2)).sqrt();
+      }
+      out
+    }
+
+    fn observation_jacobian(&self, state: &Vector<f64, 2>) -> Matrix<f64, 2, 2> {
+      let range = self.observation(state);
+      let mut out = Matrix::zeros();
+      for (i, &(bx, by)) in self.beacons.iter().enumerate() {
+        out[(i, 0)] = (state[(0, 0)] - bx) / range[(i, 0)];
+        out[(i, 1)] = (state[(1, 0)] - by) / range[(i, 0)];
+      }
+      out
+    }
+  }
+
+  #[test]
+  fn test_range_model_f64() {
+    let model = RangeModel { beacons: [(0.0, 0.0), (10.0, 0.0)] };
+    let truth = Matrix::new([[3.0], [4.0]]);
+    let measurement = model.observation(&truth);
+
+    let mut filter = ExtendedKalmanFilter::new(
+      Matrix::new([[5.0], [2.0]]),
+      Matrix::new([[10.0, 0.0], [0.0, 10.0]]),
+      Matrix::new([[1E-3, 0.0], [0.0, 1E-3]]),
+      Matrix::new([[1E-3, 0.0], [0.0, 1E-3]]),
+    );
+
+    for _i in 0..50 {
+      filter.predict(&model);
+      filter.update(&model, &measurement).unwrap();
+    }
+    println!("state: {:?}", filter.state);
+    assert!((filter.state[(0, 0)] - 3.0).abs() < 1E-3);
+    assert!((filter.state[(1, 0)] - 4.0).abs() < 1E-3);
+  }
+
+  #[test]
+  fn test_squared_observation_closures_i32f32() {
+    type TestType = I32F32;
+    // a scalar state observed through h(x) = x^2
+    let mut filter = ExtendedKalmanFilter::new(
+      Matrix::new([[TestType::from_num(2)]]),
+      Matrix::new([[TestType::from_num(1)]]),
+      Matrix::new([[TestType::from_num(1E-3)]]),
+      Matrix::new([[TestType::from_num(1E-2)]]),
+    );
+    let measurement = Matrix::new([[TestType::from_num(9)]]);
+
+    for _i in 0..50 {
+      filter.predict_with(|x| *x, |_x| Matrix::identity());
+      filter.update_with(
+        &measurement,
+        |x| Matrix::new([[x[(0, 0)] * x[(0, 0)]]]),
+        |x| Matrix::new([[x[(0, 0)] * TestType::from_num(2)]]),
+      ).unwrap();
+    }
+    println!("state: {}", filter.state[(0, 0)]);
+    assert!((filter.state[(0, 0)] - TestType::from_num(3)).abs() < TestType::from_num(1E-3));
+  }
+}
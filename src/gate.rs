@@ -0,0 +1,201 @@
+use crate::{KalmanState, Scalar};
+
+/// How observations that fall outside an innovation gate are treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GateMode {
+  /// Discard the observation entirely
+  Reject,
+  /// Inflate the measurement variance so the observation lands on the gate boundary
+  DownWeight,
+}
+
+/// The outcome of a gated update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GateOutcome {
+  /// The observation was inside the gate and was fused normally
+  Accepted,
+  /// The observation was outside the gate and was fused with reduced weight
+  DownWeighted,
+  /// The observation was outside the gate and was discarded
+  Rejected,
+}
+
+impl GateOutcome {
+  /// Whether the observation contributed to the estimate at all
+  pub fn is_accepted(&self) -> bool {
+    *self != GateOutcome::Rejected
+  }
+}
+
+/// A chi-square gate on the normalized innovation squared:
+///   residual^2 / (uncertainty + measurement_variance) <= threshold
+/// For a single variable, a threshold of 3.84 accepts 95% of
+/// well-modelled observations, 6.63 accepts 99%, and 9.0 is a 3-sigma gate.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InnovationGate<T> {
+  pub threshold: T,
+  pub mode: GateMode,
+}
+
+impl<T> InnovationGate<T>
+  where T: Scalar
+{
+  /// A threshold of zero rejects every observation
+  pub fn new(threshold: T, mode: GateMode) -> Self {
+    InnovationGate { threshold: threshold.abs(), mode }
+  }
+}
+
+impl<T> KalmanState<T>
+  where T: Scalar
+{
+  /// Update step with outlier rejection:
+  /// observations whose normalized innovation exceeds the gate threshold
+  /// are rejected or down-weighted according to the gate mode.
+  pub fn update_gated(&mut self, observation: T, gate: &InnovationGate<T>) -> GateOutcome {
+    self.update_gated_with_variance(observation, self.measurement_variance, gate)
+  }
+
+  /// Gated update step, using the given measurement variance
+  /// for this observation instead of the one configured at construction.
+  pub fn update_gated_with_variance(
+    &mut self,
+    observation: T,
+    measurement_variance: T,
+    gate: &InnovationGate<T>) -> GateOutcome
+  {
+    if gate.threshold == T::zero() {
+      return GateOutcome::Rejected;
+    }
+    let measurement_variance = measurement_variance.abs();
+    let innovation_variance = self.uncertainty + measurement_variance;
+    let residual = observation.abs_diff(self.estimate);
+
+    // compare |residual| against the gate radius, which avoids squaring
+    // a potentially large residual in narrow fixed-point types;
+    // the radius is factored so a large innovation variance cannot overflow either
+    let gate_radius = gate.threshold.sqrt() * innovation_variance.sqrt();
+    if residual <= gate_radius {
+      self.update_with_variance(observation, measurement_variance);
+      return GateOutcome::Accepted;
+    }
+
+    match gate.mode {
+      GateMode::Reject => GateOutcome::Rejected,
+      GateMode::DownWeight => {
+        // a zero innovation variance leaves no way to weight the observation
+        if gate_radius == T::zero() {
+          return GateOutcome::Rejected;
+        }
+        // inflate the innovation variance by (residual / gate_radius)^2, which
+        // puts the normalized innovation on the gate. The gain is formed from
+        // the inverse ratio so that the large residual is never squared,
+        // and the step as gain * residual = base_gain * shrink * gate_radius,
+        // which keeps its precision when the gain itself is tiny.
+        let shrink = gate_radius / residual;
+        let base_gain = self.uncertainty / innovation_variance;
+        let step = base_gain * shrink * gate_radius;
+        self.estimate =
+          if observation >= self.estimate { self.estimate + step }
+          else { self.estimate - step };
+        self.uncertainty = (T::one() - base_gain * shrink * shrink) * self.uncertainty;
+        GateOutcome::DownWeighted
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::{I16F16, I32F32};
+
+  #[test]
+  fn test_reject_glitch_f64() {
+    let gate = InnovationGate::new(9.0f64, GateMode::Reject);
+    let mut kstate = KalmanState::new(100.0f64, 1E-2, 1E-2, 1E-4);
+
+    for i in 0..50 {
+      kstate.predict();
+      // every tenth reading is a wild glitch
+      let observation = if i % 10 == 5 { 1_000.0 } else { 100.0 };
+      let outcome = kstate.update_gated(observation, &gate);
+      assert_eq!(outcome.is_accepted(), i % 10 != 5);
+    }
+    println!("est: {} uncert: {}", kstate.estimate, kstate.uncertainty);
+    assert!((kstate.estimate - 100.0).abs() < 1E-6);
+  }
+
+  #[test]
+  fn test_down_weight_i16f16() {
+    type TestType = I16F16;
+    let gate = InnovationGate::new(TestType::from_num(9), GateMode::DownWeight);
+    let mut kstate = KalmanState::new(
+      TestType::from_num(10),
+      TestType::from_num(0.01),
+      TestType::from_num(0.01),
+      TestType::from_num(0),
+    );
+    let mut ungated = kstate;
+
+    let outcome = kstate.update_gated(TestType::from_num(20), &gate);
+    ungated.update(TestType::from_num(20));
+    println!("gated: {} ungated: {}", kstate.estimate, ungated.estimate);
+    assert_eq!(outcome, GateOutcome::DownWeighted);
+    // the down-weighted step moves no further than the gate radius allows
+    assert!(kstate.estimate > TestType::from_num(10));
+    assert!(kstate.estimate < TestType::from_num(10.1));
+    assert!(ungated.estimate > TestType::from_num(14.9));
+  }
+
+  #[test]
+  fn test_down_weight_large_glitch_i16f16() {
+    type TestType = I16F16;
+    let gate = InnovationGate::new(TestType::from_num(9), GateMode::DownWeight);
+    let mut kstate = KalmanState::new(
+      TestType::from_num(0),
+      TestType::from_num(0.01),
+      TestType::from_num(0.01),
+      TestType::from_num(0),
+    );
+    // squaring this residual would overflow I16F16
+    let outcome = kstate.update_gated(TestType::from_num(1000), &gate);
+    println!("est: {} uncert: {}", kstate.estimate, kstate.uncertainty);
+    assert_eq!(outcome, GateOutcome::DownWeighted);
+    // the step is bounded by the gate radius, sqrt(9 * 0.02) ~= 0.42
+    assert!(kstate.estimate > TestType::from_num(0));
+    assert!(kstate.estimate < TestType::from_num(0.43));
+  }
+
+  #[test]
+  fn test_large_innovation_variance_i16f16() {
+    type TestType = I16F16;
+    let gate = InnovationGate::new(TestType::from_num(9), GateMode::Reject);
+    let mut kstate = KalmanState::new(
+      TestType::from_num(0),
+      TestType::from_num(5000),
+      TestType::from_num(1),
+      TestType::from_num(0),
+    );
+    // threshold * innovation variance would overflow I16F16
+    assert_eq!(kstate.update_gated(TestType::from_num(100), &gate), GateOutcome::Accepted);
+    assert!(kstate.estimate > TestType::from_num(99));
+  }
+
+  #[test]
+  fn test_zero_threshold_rejects_i32f32() {
+    type TestType = I32F32;
+    let gate = InnovationGate::new(TestType::from_num(0), GateMode::DownWeight);
+    let mut kstate = KalmanState::new(
+      TestType::from_num(5),
+      TestType::from_num(1),
+      TestType::from_num(1),
+      TestType::from_num(0),
+    );
+    assert_eq!(kstate.update_gated(TestType::from_num(6), &gate), GateOutcome::Rejected);
+    assert_eq!(kstate.estimate, TestType::from_num(5));
+  }
+}
@@ -0,0 +1,230 @@
+use core::f64::consts::PI;
+
+use crate::{KalmanError, KalmanState, Scalar};
+
+/// Noise variances identified from a recorded observation series,
+/// together with the filter state after running over the whole record
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseIdentification<T> {
+  /// Maximum-likelihood estimate of the measurement variance
+  pub measurement_variance: T,
+  /// Maximum-likelihood estimate of the process variance (per sample)
+  pub process_variance: T,
+  /// Filtered estimate after the last observation
+  pub estimate: T,
+  /// Uncertainty in the estimate after the last observation
+  pub uncertainty: T,
+  /// Innovation log-likelihood of the record at the identified variances
+  pub log_likelihood: f64,
+}
+
+impl<T> NoiseIdentification<T>
+  where T: Scalar
+{
+  /// A filter configured with the identified variances, ready to continue
+  /// from the end of the record on live observations
+  pub fn to_kalman(&self) -> KalmanState<T> {
+    KalmanState::new(self.estimate, self.uncertainty, self.measurement_variance, self.process_variance)
+  }
+}
+
+/// Result of filtering a record in units of the measurement variance
+struct Profile {
+  log_likelihood: f64,
+  measurement_variance: f64,
+  estimate: f64,
+  scaled_uncertainty: f64,
+}
+
+/// Filter the record with unit measurement variance and the given ratio of
+/// process to measurement variance, concentrating the measurement variance
+/// out of the likelihood (its maximum-likelihood value has a closed form).
+fn profile<T: Scalar>(observations: &[T], ratio: f64) -> Profile {
+  let mut estimate = observations[0].to_f64();
+  let mut uncertainty = 1.0;
+  let mut weighted_sum = 0.0;
+  let mut log_det = 0.0;
+  for observation in &observations[1..] {
+    uncertainty += ratio;
+    let innovation_variance = uncertainty + 1.0;
+    let innovation = observation.to_f64() - estimate;
+    weighted_sum += innovation * innovation / innovation_variance;
+    log_det += num_traits::Float::ln(innovation_variance);
+    let gain = uncertainty / innovation_variance;
+    estimate += gain * innovation;
+    uncertainty *= 1.0 - gain;
+  }
+  let count = (observations.len() - 1) as f64;
+  let measurement_variance = (weighted_sum / count).max(f64::MIN_POSITIVE);
+  let log_likelihood = -0.5 * (
+    count * num_traits::Float::ln(2.0 * PI * measurement_variance) + log_det + count);
+  Profile { log_likelihood, measurement_variance, estimate, scaled_uncertainty: uncertainty }
+}
+
+/// Gaussian log-likelihood of the innovations produced by a filter with the
+/// given variances over a recorded series of observations, one predict and one
+/// update per observation. The filter is started from the first observation
+/// with uncertainty equal to the measurement variance, so at least two
+/// observations are required. Larger is better: this can be used to compare
+/// candidate tunings.
+pub fn innovation_log_likelihood<T>(observations: &[T], measurement_variance: T, process_variance: T)
+  -> Result<f64, KalmanError>
+  where T: Scalar
+{
+  if observations.len() < 2 {
+    return Err(KalmanError::InsufficientData);
+  }
+  let r = measurement_variance.to_f64().abs();
+  let q = process_variance.to_f64().abs();
+  let mut estimate = observations[0].to_f64();
+  let mut uncertainty = r;
+  let mut log_likelihood = 0.0;
+  for observation in &observations[1..] {
+    uncertainty += q;
+    let innovation_variance = uncertainty + r;
+    if innovation_variance <= 0.0 {
+      return Err(KalmanError::SingularMatrix);
+    }
+    let innovation = observation.to_f64() - estimate;
+    log_likelihood -= 0.5 * (num_traits::Float::ln(2.0 * PI * innovation_variance)
+      + innovation * innovation / innovation_variance);
+    let gain = uncertainty / innovation_variance;
+    estimate += gain * innovation;
+    uncertainty *= 1.0 - gain;
+  }
+  Ok(log_likelihood)
+}
+
+/// Estimate the measurement and process variances of a random-walk
+/// `KalmanState` from a recorded series of observations, one per predict step,
+/// by maximizing the innovation log-likelihood.
+///
+/// The measurement variance is solved in closed form for each candidate
+/// ratio of process to measurement variance, and the ratio is found by a
+/// coarse logarithmic search (from 1E-8 to 1E4) refined by golden-section search.
+/// Only a forward pass is run per candidate, so no allocation is needed.
+/// The search itself is done in `f64`; very small variances may round to zero
+/// in narrow fixed-point types.
+pub fn identify_noise<T>(observations: &[T]) -> Result<NoiseIdentification<T>, KalmanError>
+  where T: Scalar
+{
+  if observations.len() < 3 {
+    return Err(KalmanError::InsufficientData);
+  }
+  const MIN_LOG_RATIO: f64 = -8.0;
+  const MAX_LOG_RATIO: f64 = 4.0;
+  const GRID_STEPS: usize = 24;
+  const REFINE_STEPS: usize = 40;
+  let log_likelihood =
+    |log_ratio: f64| profile(observations, num_traits::Float::powf(10.0, log_ratio)).log_likelihood;
+
+  // coarse grid over log10(q / r)
+  let grid_step = (MAX_LOG_RATIO - MIN_LOG_RATIO) / GRID_STEPS as f64;
+  let mut best = MIN_LOG_RATIO;
+  let mut best_likelihood = f64::NEG_INFINITY;
+  for i in 0..=GRID_STEPS {
+    let log_ratio = MIN_LOG_RATIO + grid_step * i as f64;
+    let likelihood = log_likelihood(log_ratio);
+    if likelihood > best_likelihood {
+      best = log_ratio;
+      best_likelihood = likelihood;
+    }
+  }
+
+  // golden-section refinement within the neighbouring grid cells
+  let inv_phi = (num_traits::Float::sqrt(5.0f64) - 1.0) / 2.0;
+  let mut lo = (best - grid_step).max(MIN_LOG_RATIO);
+  let mut hi = (best + grid_step).min(MAX_LOG_RATIO);
+  let mut a = hi - inv_phi * (hi - lo);
+  let mut b = lo + inv_phi * (hi - lo);
+  let mut fa = log_likelihood(a);
+  let mut fb = log_likelihood(b);
+  for _i in 0..REFINE_STEPS {
+    if fa > fb {
+      hi = b;
+      b = a;
+      fb = fa;
+      a = hi - inv_phi * (hi - lo);
+      fa = log_likelihood(a);
+    }
+    else {
+      lo = a;
+      a = b;
+      fa = fb;
+      b = lo + inv_phi * (hi - lo);
+      fb = log_likelihood(b);
+    }
+  }
+  let refined = (lo + hi) / 2.0;
+  let log_ratio = if log_likelihood(refined) > best_likelihood { refined } else { best };
+
+  let ratio = num_traits::Float::powf(10.0, log_ratio);
+  let result = profile(observations, ratio);
+  let r = result.measurement_variance;
+  Ok(NoiseIdentification {
+    measurement_variance: T::from_f64(r),
+    process_variance: T::from_f64(ratio * r),
+    estimate: T::from_f64(result.estimate),
+    uncertainty: T::from_f64(result.scaled_uncertainty * r),
+    log_likelihood: result.log_likelihood,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+  use rand_distr::{Distribution, Normal};
+
+  const COUNT: usize = 5000;
+
+  /// A random walk with process variance 1E-3, observed with variance 0.04
+  fn random_walk(seed: u64) -> [f64; COUNT] {
+    let step = Normal::new(0.0f64, 1E-3f64.sqrt()).unwrap();
+    let noise = Normal::new(0.0f64, 0.2).unwrap();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut truth = 10.0;
+    let mut observations = [0.0; COUNT];
+    for observation in observations.iter_mut() {
+      truth += step.sample(&mut rng);
+      *observation = truth + noise.sample(&mut rng);
+    }
+    observations
+  }
+
+  #[test]
+  fn test_identify_random_walk_f64() {
+    let observations = random_walk(23);
+    let identified = identify_noise(&observations).unwrap();
+    println!("identified: {:?}", identified);
+    assert!((identified.measurement_variance - 0.04).abs() < 0.004);
+    assert!((identified.process_variance - 1E-3).abs() < 3E-4);
+
+    // the identified tuning beats nearby alternatives
+    let r = identified.measurement_variance;
+    let q = identified.process_variance;
+    let best = innovation_log_likelihood(&observations, r, q).unwrap();
+    assert!((best - identified.log_likelihood).abs() < 1E-6);
+    assert!(best > innovation_log_likelihood(&observations, r * 2.0, q).unwrap());
+    assert!(best > innovation_log_likelihood(&observations, r, q / 2.0).unwrap());
+
+    let kstate = identified.to_kalman();
+    assert_eq!(kstate.measurement_variance(), r);
+    assert_eq!(kstate.estimate, identified.estimate);
+  }
+
+  #[test]
+  fn test_identify_i32f32() {
+    type TestType = I32F32;
+    let observations = random_walk(24).map(TestType::from_num);
+    let identified = identify_noise(&observations).unwrap();
+    println!("identified: {:?}", identified);
+    assert!((identified.measurement_variance - TestType::from_num(0.04)).abs() < TestType::from_num(0.004));
+    assert!((identified.process_variance - TestType::from_num(1E-3)).abs() < TestType::from_num(3E-4));
+
+    assert_eq!(identify_noise(&observations[..2]).unwrap_err(), KalmanError::InsufficientData);
+  }
+}
@@ -0,0 +1,238 @@
+use core::ops::Add;
+
+use crate::{KalmanState, Scalar};
+
+/// A single-variable Kalman filter in information (inverse covariance) form.
+/// Fusing observations becomes simple addition of information contributions,
+/// which is cheap and commutative when aggregating readings from many nodes.
+/// Note that the information of a very certain estimate can be large,
+/// so narrow fixed-point types need care with small variances.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "InformationStateData<T>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct InformationState<T> {
+  /// Information: the inverse of the uncertainty (variance)
+  pub information: T,
+  /// Information-weighted estimate: estimate / uncertainty
+  pub information_estimate: T,
+  measurement_variance: T,  // Uncertainty in the measurement itself
+  process_variance: T,      // Error introduced by uncertainty in the process (model)
+}
+
+/// Unvalidated `InformationState` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct InformationStateData<T> {
+  information: T,
+  information_estimate: T,
+  measurement_variance: T,
+  process_variance: T,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<InformationStateData<T>> for InformationState<T>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: InformationStateData<T>) -> Result<Self, Self::Error> {
+    let zero = T::zero();
+    if data.information < zero || data.measurement_variance < zero || data.process_variance < zero {
+      return Err("negative information or variance");
+    }
+    Ok(InformationState {
+      information: data.information,
+      information_estimate: data.information_estimate,
+      measurement_variance: data.measurement_variance,
+      process_variance: data.process_variance,
+    })
+  }
+}
+
+/// The information carried by one observation (or the sum of several),
+/// ready to be added to an `InformationState`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InformationContribution<T> {
+  /// Inverse of the measurement variance
+  pub information: T,
+  /// Observation divided by the measurement variance
+  pub information_estimate: T,
+}
+
+impl<T> InformationContribution<T>
+  where T: Scalar
+{
+  pub fn zero() -> Self {
+    InformationContribution { information: T::zero(), information_estimate: T::zero() }
+  }
+
+  /// The contribution of a single observation with the given (non-zero) variance
+  pub fn from_observation(observation: T, measurement_variance: T) -> Self {
+    let measurement_variance = measurement_variance.abs();
+    InformationContribution {
+      information: T::one() / measurement_variance,
+      information_estimate: observation / measurement_variance,
+    }
+  }
+}
+
+impl<T> Add for InformationContribution<T>
+  where T: Scalar
+{
+  type Output = Self;
+
+  fn add(self, rhs: Self) -> Self {
+    InformationContribution {
+      information: self.information + rhs.information,
+      information_estimate: self.information_estimate + rhs.information_estimate,
+    }
+  }
+}
+
+impl<T> InformationState<T>
+  where T: Scalar
+{
+  pub fn new(
+    information: T,
+    information_estimate: T,
+    measurement_variance: T,
+    process_variance: T) -> InformationState<T>
+  {
+    InformationState {
+      information: information.abs(),
+      information_estimate,
+      measurement_variance: measurement_variance.abs(),
+      process_variance: process_variance.abs(),
+    }
+  }
+
+  /// Convert from covariance form.
+  /// Returns `None` if the uncertainty is zero (infinite information).
+  pub fn from_kalman(state: &KalmanState<T>) -> Option<InformationState<T>> {
+    if state.uncertainty == T::zero() {
+      return None;
+    }
+    Some(InformationState {
+      information: T::one() / state.uncertainty,
+      information_estimate: state.estimate / state.uncertainty,
+      measurement_variance: state.measurement_variance,
+      process_variance: state.process_variance,
+    })
+  }
+
+  /// Convert to covariance form.
+  /// Returns `None` if the information is zero (nothing is known yet).
+  pub fn to_kalman(&self) -> Option<KalmanState<T>> {
+    if self.information == T::zero() {
+      return None;
+    }
+    Some(KalmanState {
+      estimate: self.information_estimate / self.information,
+      uncertainty: T::one() / self.information,
+      measurement_variance: self.measurement_variance,
+      process_variance: self.process_variance,
+    })
+  }
+
+  /// Predict step:
+  /// inflate the uncertainty by the process variance, which in information
+  /// form scales both terms by 1 / (1 + process_variance * information).
+  pub fn predict(&mut self) {
+    self.predict_dt(T::one());
+  }
+
+  /// Predict step with a variable time step:
+  /// the process variance is treated as a rate (per unit of time)
+  /// and scaled by the elapsed time `dt` since the last step.
+  pub fn predict_dt(&mut self, dt: T) {
+    let factor = T::one() + self.process_variance * dt.abs() * self.information;
+    self.information = self.information / factor;
+    self.information_estimate = self.information_estimate / factor;
+  }
+
+  /// Update step: add the information from a single observation,
+  /// using the measurement variance configured at construction.
+  pub fn update(&mut self, observation: T) {
+    self.update_with_variance(observation, self.measurement_variance);
+  }
+
+  /// Update step: add the information from a single observation
+  /// with the given measurement variance.
+  pub fn update_with_variance(&mut self, observation: T, measurement_variance: T) {
+    self.add_contribution(&InformationContribution::from_observation(observation, measurement_variance));
+  }
+
+  /// Update step: add a (possibly pre-summed) information contribution.
+  pub fn add_contribution(&mut self, contribution: &InformationContribution<T>) {
+    self.information = self.information + contribution.information;
+    self.information_estimate = self.information_estimate + contribution.information_estimate;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+
+  #[test]
+  fn test_matches_covariance_form_f64() {
+    let kstate = KalmanState::new(2.0f64, 0.5, 0.2, 0.01);
+    let mut covariance = kstate;
+    let mut information = InformationState::from_kalman(&kstate).unwrap();
+
+    for i in 0..20 {
+      let observation = 3.0 + (i % 3) as f64 * 0.1;
+      covariance.predict();
+      covariance.update(observation);
+      information.predict();
+      information.update(observation);
+    }
+    let converted = information.to_kalman().unwrap();
+    println!("cov: {} info: {}", covariance.estimate, converted.estimate);
+    assert!((covariance.estimate - converted.estimate).abs() < 1E-12);
+    assert!((covariance.uncertainty - converted.uncertainty).abs() < 1E-12);
+  }
+
+  #[test]
+  fn test_commutative_fusion_i32f32() {
+    type TestType = I32F32;
+    let readings = [(10.0, 0.5), (11.0, 1.0), (9.5, 0.25), (10.5, 2.0)];
+
+    let forward = readings.iter()
+      .map(|&(z, r)| InformationContribution::from_observation(TestType::from_num(z), TestType::from_num(r)))
+      .fold(InformationContribution::zero(), |acc, c| acc + c);
+    let backward = readings.iter().rev()
+      .map(|&(z, r)| InformationContribution::from_observation(TestType::from_num(z), TestType::from_num(r)))
+      .fold(InformationContribution::zero(), |acc, c| acc + c);
+    assert_eq!(forward, backward);
+
+    // start from no prior knowledge at all
+    let mut state = InformationState::new(
+      TestType::from_num(0),
+      TestType::from_num(0),
+      TestType::from_num(1),
+      TestType::from_num(0),
+    );
+    assert!(state.to_kalman().is_none());
+    state.add_contribution(&forward);
+    let kstate = state.to_kalman().unwrap();
+    println!("est: {} uncert: {}", kstate.estimate, kstate.uncertainty);
+    // weighted mean: (20 + 11 + 38 + 5.25) / 7.5
+    assert!((kstate.estimate - TestType::from_num(9.9)).abs() < TestType::from_num(1E-6));
+    assert!((kstate.uncertainty - TestType::from_num(1.0 / 7.5)).abs() < TestType::from_num(1E-6));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_rejects_negative_information() {
+    let json = r#"{"information":4.0,"information_estimate":2.0,"measurement_variance":0.5,"process_variance":0.01}"#;
+    assert!(serde_json::from_str::<InformationState<f64>>(json).is_ok());
+    let json = r#"{"information":-4.0,"information_estimate":2.0,"measurement_variance":0.5,"process_variance":0.01}"#;
+    assert!(serde_json::from_str::<InformationState<f64>>(json).is_err());
+    let json = r#"{"information":4.0,"information_estimate":2.0,"measurement_variance":0.5,"process_variance":-0.01}"#;
+    assert!(serde_json::from_str::<InformationState<f64>>(json).is_err());
+  }
+}
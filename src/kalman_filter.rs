@@ -0,0 +1,200 @@
+use num_traits::Signed;
+
+use crate::{KalmanError, Matrix, Scalar, Vector};
+
+/// A linear Kalman filter with `N` state variables, `M` measured variables,
+/// and `U` control inputs. All matrices are stack-allocated.
+///
+/// The model is:
+///   x' = F x + B u + w,  w ~ N(0, Q)
+///   z  = H x + v,        v ~ N(0, R)
+///
+/// Requires a signed Scalar type, since innovations and
+/// covariance terms may be negative.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KalmanFilter<T, const N: usize, const M: usize, const U: usize = 0> {
+  /// Estimated state x
+  pub state: Vector<T, N>,
+  /// Estimated state covariance P
+  pub covariance: Matrix<T, N, N>,
+  /// State transition model F
+  pub transition: Matrix<T, N, N>,
+  /// Control input model B
+  pub control: Matrix<T, N, U>,
+  /// Observation model H
+  pub observation: Matrix<T, M, N>,
+  /// Process noise covariance Q
+  pub process_noise: Matrix<T, N, N>,
+  /// Measurement noise covariance R
+  pub measurement_noise: Matrix<T, M, M>,
+}
+
+impl<T, const N: usize, const M: usize> KalmanFilter<T, N, M, 0>
+  where T: Scalar + Signed
+{
+  /// Create a filter without control inputs.
+  /// Use `with_control` to attach a control input model.
+  pub fn new(
+    state: Vector<T, N>,
+    covariance: Matrix<T, N, N>,
+    transition: Matrix<T, N, N>,
+    observation: Matrix<T, M, N>,
+    process_noise: Matrix<T, N, N>,
+    measurement_noise: Matrix<T, M, M>) -> Self
+  {
+    KalmanFilter {
+      state,
+      covariance,
+      transition,
+      control: Matrix([[]; N]),
+      observation,
+      process_noise,
+      measurement_noise,
+    }
+  }
+
+  /// Attach a control input model B with `V` control inputs
+  pub fn with_control<const V: usize>(self, control: Matrix<T, N, V>) -> KalmanFilter<T, N, M, V> {
+    KalmanFilter {
+      state: self.state,
+      covariance: self.covariance,
+      transition: self.transition,
+      control,
+      observation: self.observation,
+      process_noise: self.process_noise,
+      measurement_noise: self.measurement_noise,
+    }
+  }
+}
+
+impl<T, const N: usize, const M: usize, const U: usize> KalmanFilter<T, N, M, U>
+  where T: Scalar + Signed
+{
+  /// Predict step without control input:
+  /// x = F x, P = F P F' + Q
+  pub fn predict(&mut self) {
+    self.state = self.transition * self.state;
+    self.covariance =
+      self.transition * self.covariance * self.transition.transpose() + self.process_noise;
+  }
+
+  /// Predict step with control input `u`:
+  /// x = F x + B u, P = F P F' + Q
+  pub fn predict_with_control(&mut self, input: &Vector<T, U>) {
+    self.predict();
+    let driven: Vector<T, N> = self.control * *input;
+    self.state = self.state + driven;
+  }
+
+  /// Update step: incorporate the measurement vector `measurement`.
+  /// Fails if the innovation covariance H P H' + R is singular,
+  /// in which case the filter is left unchanged.
+  pub fn update(&mut self, measurement: &Vector<T, M>) -> Result<(), KalmanError> {
+    let innovation = *measurement - self.observation * self.state;
+    correct(
+      &mut self.state,
+      &mut self.covariance,
+      &innovation,
+      &self.observation,
+      &self.measurement_noise,
+    )
+  }
+}
+
+/// Shared measurement correction for the matrix filters:
+/// apply the `innovation` through observation model (or Jacobian) `h`
+/// with measurement noise `r`. Leaves the state unchanged on error.
+pub(crate) fn correct<T, const N: usize, const M: usize>(
+  state: &mut Vector<T, N>,
+  covariance: &mut Matrix<T, N, N>,
+  innovation: &Vector<T, M>,
+  h: &Matrix<T, M, N>,
+  r: &Matrix<T, M, M>) -> Result<(), KalmanError>
+  where T: Scalar + Signed
+{
+  let h_t = h.transpose();
+  let innovation_covariance = *h * *covariance * h_t + *r;
+  let inv = innovation_covariance.inverse().ok_or(KalmanError::SingularMatrix)?;
+  let gain: Matrix<T, N, M> = *covariance * h_t * inv;
+  let correction: Vector<T, N> = gain * *innovation;
+
+  *state = *state + correction;
+  // Joseph form keeps the covariance symmetric and positive semi-definite
+  let i_kh = Matrix::<T, N, N>::identity() - gain * *h;
+  *covariance = i_kh * *covariance * i_kh.transpose() + gain * *r * gain.transpose();
+  Ok(())
+
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+
+  #[test]
+  fn test_constant_velocity_f64() {
+    let dt = 0.5f64;
+    let mut filter = KalmanFilter::new(
+      Matrix::new([[0.0], [0.0]]),
+      Matrix::new([[1.0, 0.0], [0.0, 1.0]]),
+      Matrix::new([[1.0, dt], [0.0, 1.0]]),
+      Matrix::new([[1.0, 0.0]]),
+      Matrix::new([[1E-6, 0.0], [0.0, 1E-6]]),
+      Matrix::new([[1E-2]]),
+    );
+
+    for i in 1..=200 {
+      filter.predict();
+      filter.update(&Matrix::new([[2.0 * dt * i as f64]])).unwrap();
+    }
+    println!("state: {:?}", filter.state);
+    assert!((filter.state[(0, 0)] - 200.0).abs() < 1E-2);
+    assert!((filter.state[(1, 0)] - 2.0).abs() < 1E-2);
+  }
+
+  #[test]
+  fn test_control_input_f64() {
+    // position driven purely by a known velocity command
+    let mut filter = KalmanFilter::new(
+      Matrix::new([[0.0f64]]),
+      Matrix::new([[1E-4]]),
+      Matrix::new([[1.0]]),
+      Matrix::new([[1.0]]),
+      Matrix::new([[0.0]]),
+      Matrix::new([[1.0]]),
+    ).with_control(Matrix::new([[1.0, -1.0]]));
+
+    for _i in 0..10 {
+      filter.predict_with_control(&Matrix::new([[3.0], [1.0]]));
+    }
+    assert!((filter.state[(0, 0)] - 20.0).abs() < 1E-9);
+  }
+
+  #[test]
+  fn test_two_sensors_i32f32() {
+    type TestType = I32F32;
+    let zero = TestType::from_num(0);
+    let one = TestType::from_num(1);
+    // one scalar state observed by two sensors of differing quality
+    let mut filter = KalmanFilter::new(
+      Matrix::new([[zero]]),
+      Matrix::new([[TestType::from_num(100)]]),
+      Matrix::new([[one]]),
+      Matrix::new([[one], [one]]),
+      Matrix::new([[TestType::from_num(1E-4)]]),
+      Matrix::new([
+        [TestType::from_num(1E-2), zero],
+        [zero, TestType::from_num(1)],
+      ]),
+    );
+
+    for _i in 0..20 {
+      filter.predict();
+      filter.update(&Matrix::new([[TestType::from_num(5)], [TestType::from_num(6)]])).unwrap();
+    }
+    println!("state: {}", filter.state[(0, 0)]);
+    // weighted towards the precise sensor
+    assert!((filter.state[(0, 0)] - TestType::from_num(5.01)).abs() < TestType::from_num(1E-2));
+  }
+}
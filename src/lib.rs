@@ -1,20 +1,107 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
 use num_traits::float::Float;
+use fixed::traits::Fixed;
+
+mod adaptive;
+mod allan;
+mod batch;
+mod clock;
+mod error;
+mod extended;
+mod gate;
+mod identify;
+mod information;
+mod kalman_filter;
+mod matrix;
+mod out_of_sequence;
+mod rate_state;
+mod report;
+mod scalar;
+#[cfg(feature = "serde")]
+mod serde_array;
+mod smoother;
+mod steady_state;
+mod unscented;
+mod wrapped;
+
+pub use adaptive::{AdaptiveState, NoiseAdaptation};
+pub use allan::{
+  allan_deviation, fit_power_law, modified_allan_deviation, overlapping_allan_deviation,
+  ClockData, PowerLawNoise,
+};
+pub use batch::fuse_batch;
+pub use clock::{ClockNoise, ClockState};
+pub use error::KalmanError;
+pub use extended::{ExtendedKalmanFilter, NonlinearModel};
+pub use gate::{GateMode, GateOutcome, InnovationGate};
+pub use identify::{identify_noise, innovation_log_likelihood, NoiseIdentification};
+pub use information::{InformationContribution, InformationState};
+pub use kalman_filter::KalmanFilter;
+pub use matrix::{Matrix, Vector};
+pub use out_of_sequence::OutOfSequenceFilter;
+pub use rate_state::KalmanRateState;
+pub use report::UpdateReport;
+pub use scalar::Scalar;
+pub use smoother::{rts_smooth, FixedLagSmoother, RtsStep};
+pub use steady_state::{steady_state_gain, SteadyStateFilter};
+pub use unscented::{SigmaPointParams, UnscentedFilter};
+pub use wrapped::WrappedState;
+
+use scalar::{blend, signed_diff};
 
 /// Holds state for a simple Kalman filter in a single variable.
-/// This can be used to fuse inputs from multiple identical "sensors".
+/// This can be used to fuse inputs from multiple identical "sensors",
+/// or from sensors of differing quality by supplying a per-observation
+/// measurement variance to the update step.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "KalmanStateData<T>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
 pub struct KalmanState<T> {
-  pub estimate: T,/// Estimated value of the variable
-  pub uncertainty: T,/// Calculated total uncertainty in the estimate
+  /// Estimated value of the variable
+  pub estimate: T,
+  /// Calculated total uncertainty in the estimate
+  pub uncertainty: T,
   measurement_variance: T,  // Uncertainty in the measurement itself
   process_variance: T,      // Error introduced by uncertainty in the process (model)
 }
 
+/// Unvalidated `KalmanState` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct KalmanStateData<T> {
+  estimate: T,
+  uncertainty: T,
+  measurement_variance: T,
+  process_variance: T,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<KalmanStateData<T>> for KalmanState<T>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: KalmanStateData<T>) -> Result<Self, Self::Error> {
+    let zero = T::zero();
+    if data.uncertainty < zero || data.measurement_variance < zero || data.process_variance < zero {
+      return Err("negative uncertainty or variance");
+    }
+    Ok(KalmanState {
+      estimate: data.estimate,
+      uncertainty: data.uncertainty,
+      measurement_variance: data.measurement_variance,
+      process_variance: data.process_variance,
+    })
+  }
+}
+
 impl<T> KalmanState<T>
-  where T: Float
+  where T: Scalar
 {
-  pub fn new_float(
+  pub fn new(
     estimate: T,
     uncertainty: T,
     measurement_variance: T,
@@ -27,36 +114,73 @@ impl<T> KalmanState<T>
       process_variance: process_variance.abs(),
     }
   }
-}
-
 
+  /// The variance of each observation, as configured or adapted
+  pub fn measurement_variance(&self) -> T {
+    self.measurement_variance
+  }
 
-/// Kalman update function (fold function) for Float types
-pub fn kalman_update_float<T>(state: &KalmanState<T>, observation: T) -> KalmanState<T>
-  where
-    T: Float,
-{
-  // Kalman gain
-  let kalman_gain = state.uncertainty / (state.uncertainty + state.measurement_variance);
+  /// The variance added to the uncertainty by each predict step
+  pub fn process_variance(&self) -> T {
+    self.process_variance
+  }
 
-  // Update estimate
-  let new_estimate = state.estimate + kalman_gain * (observation - state.estimate);
+  /// Predict step:
+  /// propagate the state forward one tick without an observation,
+  /// inflating the uncertainty by the process variance.
+  pub fn predict(&mut self) {
+    self.uncertainty = self.uncertainty + self.process_variance;
+  }
 
-  // Update uncertainty
-  let mut new_uncertainty = (T::one() - kalman_gain) * state.uncertainty;
-  // adjust for process variance (normally done in a "predict" step
-  // new_uncertainty = new_uncertainty + state.process_variance;
-  new_uncertainty = new_uncertainty + state.process_variance*(state.estimate - new_estimate).abs();
+  /// Predict step with a variable time step:
+  /// the process variance is treated as a rate (per unit of time)
+  /// and scaled by the elapsed time `dt` since the last step.
+  pub fn predict_dt(&mut self, dt: T) {
+    self.uncertainty = self.uncertainty + self.process_variance * dt.abs();
+  }
 
-  KalmanState::new_float(new_estimate, new_uncertainty,
-                         state.measurement_variance,state.process_variance)
+  /// Update step:
+  /// incorporate a single observation into the estimate.
+  /// Returns the gain and innovation details of the step.
+  pub fn update(&mut self, observation: T) -> UpdateReport<T> {
+    self.update_with_variance(observation, self.measurement_variance)
+  }
 
+  /// Update step, using the given measurement variance
+  /// for this observation instead of the one configured at construction.
+  /// This allows fusing readings from sensors of differing quality.
+  /// Returns the gain and innovation details of the step.
+  pub fn update_with_variance(&mut self, observation: T, measurement_variance: T) -> UpdateReport<T> {
+    let innovation_variance = self.uncertainty + measurement_variance.abs();
+    let kalman_gain = self.uncertainty / innovation_variance;
+    let innovation = signed_diff(observation, self.estimate);
+    self.estimate = blend(self.estimate, observation, kalman_gain);
+    self.uncertainty = (T::one() - kalman_gain) * self.uncertainty;
+
+    UpdateReport {
+      innovation,
+      innovation_variance,
+      gain: kalman_gain,
+      residual: signed_diff(observation, self.estimate),
+    }
+  }
 }
 
-use fixed::traits::{ Fixed };
+impl<T> KalmanState<T>
+  where T: Float + Scalar
+{
+  pub fn new_float(
+    estimate: T,
+    uncertainty: T,
+    measurement_variance: T,
+    process_variance: T) -> KalmanState<T>
+  {
+    KalmanState::new(estimate, uncertainty, measurement_variance, process_variance)
+  }
+}
 
 impl<T> KalmanState<T>
-  where T: Fixed
+  where T: Fixed + Scalar
 {
   pub fn new_fixed (
     estimate: T,
@@ -64,54 +188,138 @@ impl<T> KalmanState<T>
     measurement_variance: T,
     process_variance: T) -> KalmanState<T>
   {
-    if T::IS_SIGNED {
-      KalmanState {
-        estimate,
-        uncertainty:
-          if uncertainty < 0 { T::ZERO - uncertainty } else { uncertainty },
+    KalmanState::new(estimate, uncertainty, measurement_variance, process_variance)
+  }
 
-        measurement_variance:
-         if measurement_variance < 0 { T::ZERO - measurement_variance } else { measurement_variance },
+  /// Predict step for Fixed types with a variable time step,
+  /// as `predict_dt` but accepting `dt` in any Fixed type,
+  /// for example a U32F32 duration in seconds.
+  /// Reports overflow if `dt` does not fit in `T`, or if the
+  /// uncertainty would overflow; on error the state is left unchanged.
+  pub fn predict_fixed_dt<D: Fixed>(&mut self, dt: D) -> Result<(), KalmanError> {
+    let dt: Option<T> =
+      if dt < 0 {
+        // D::MIN cannot be negated in D, but its magnitude may still fit in T
+        dt.checked_neg().and_then(T::checked_from_num)
+          .or_else(|| T::checked_from_num(dt).and_then(|dt| dt.checked_neg()))
+      }
+      else { T::checked_from_num(dt) };
+    let dt = dt.ok_or(KalmanError::Overflow)?;
+    self.uncertainty = self.process_variance.checked_mul(dt)
+      .and_then(|growth| self.uncertainty.checked_add(growth))
+      .ok_or(KalmanError::Overflow)?;
+    Ok(())
+  }
+
+  /// Predict step for Fixed types that reports overflow
+  /// instead of panicking (debug) or wrapping (release).
+  /// On error the state is left unchanged.
+  pub fn checked_predict(&mut self) -> Result<(), KalmanError> {
+    self.uncertainty = self.uncertainty.checked_add(self.process_variance)
+      .ok_or(KalmanError::Overflow)?;
+    Ok(())
+  }
+
+  /// Predict step for Fixed types that saturates at the bounds of the type
+  pub fn saturating_predict(&mut self) {
+    self.uncertainty = self.uncertainty.saturating_add(self.process_variance);
+  }
 
-        process_variance:
-          if process_variance < 0 { T::ZERO - process_variance } else { process_variance },
+  /// Update step for Fixed types that reports overflow
+  /// instead of panicking (debug) or wrapping (release).
+  /// On error the state is left unchanged.
+  pub fn checked_update(&mut self, observation: T) -> Result<(), KalmanError> {
+    let denom = self.uncertainty.checked_add(self.measurement_variance)
+      .ok_or(KalmanError::Overflow)?;
+    let kalman_gain =
+      if denom == T::ZERO { T::ZERO }
+      else { self.uncertainty.checked_div(denom).ok_or(KalmanError::Overflow)? };
+
+    // Unsigned types cannot represent a negative residual
+    let new_estimate =
+      if observation >= self.estimate {
+        observation.checked_sub(self.estimate)
+          .and_then(|residual| kalman_gain.checked_mul(residual))
+          .and_then(|step| self.estimate.checked_add(step))
       }
-    }
-    else {
-      KalmanState {
-        estimate,
-        uncertainty,
-        measurement_variance,
-        process_variance,
+      else {
+        self.estimate.checked_sub(observation)
+          .and_then(|residual| kalman_gain.checked_mul(residual))
+          .and_then(|step| self.estimate.checked_sub(step))
       }
-    }
+      .ok_or(KalmanError::Overflow)?;
+
+    let new_uncertainty = T::one().checked_sub(kalman_gain)
+      .and_then(|factor| factor.checked_mul(self.uncertainty))
+      .ok_or(KalmanError::Overflow)?;
+
+    self.estimate = new_estimate;
+    self.uncertainty = new_uncertainty;
+    Ok(())
+  }
+
+  /// Update step for Fixed types that saturates at the bounds of the type
+  /// rather than panicking (debug) or wrapping (release).
+  pub fn saturating_update(&mut self, observation: T) {
+    let denom = self.uncertainty.saturating_add(self.measurement_variance);
+    let kalman_gain =
+      if denom == T::ZERO { T::ZERO }
+      else { self.uncertainty.saturating_div(denom) };
+
+    // Unsigned types cannot represent a negative residual
+    self.estimate =
+      if observation >= self.estimate {
+        let residual = observation.saturating_sub(self.estimate);
+        self.estimate.saturating_add(kalman_gain.saturating_mul(residual))
+      }
+      else {
+        let residual = self.estimate.saturating_sub(observation);
+        self.estimate.saturating_sub(kalman_gain.saturating_mul(residual))
+      };
+
+    self.uncertainty = T::one().saturating_sub(kalman_gain).saturating_mul(self.uncertainty);
   }
 }
 
+/// Kalman update function (fold function) for any Scalar type
+pub fn kalman_update<T>(state: &KalmanState<T>, observation: T) -> KalmanState<T>
+  where
+    T: Scalar,
+{
+  kalman_update_with_report(state, observation).0
+}
+
+/// Kalman update function (fold function) for any Scalar type,
+/// also returning the gain and innovation details of the step
+pub fn kalman_update_with_report<T>(state: &KalmanState<T>, observation: T)
+  -> (KalmanState<T>, UpdateReport<T>)
+  where
+    T: Scalar,
+{
+  let mut new_state = *state;
+  let report = new_state.update(observation);
+
+  // adjust for process variance (normally done in a "predict" step),
+  // in proportion to how far the estimate moved
+  let est_diff = state.estimate.abs_diff(new_state.estimate);
+  new_state.uncertainty = new_state.uncertainty + state.process_variance * est_diff;
+  (new_state, report)
+}
 
+/// Kalman update function (fold function) for Float types
+pub fn kalman_update_float<T>(state: &KalmanState<T>, observation: T) -> KalmanState<T>
+  where
+    T: Float + Scalar,
+{
+  kalman_update(state, observation)
+}
 
 /// Kalman update function (fold function) for Fixed types
 pub fn kalman_update_fixed<T>(state: &KalmanState<T>, observation: T) -> KalmanState<T>
   where
-    T: Fixed ,
+    T: Fixed + Scalar,
 {
-  // Kalman gain
-  let kalman_gain = state.uncertainty / (state.uncertainty + state.measurement_variance);
-
-  // Update estimate
-  let new_estimate = state.estimate + kalman_gain * (observation - state.estimate);
-
-  // Update uncertainty
-  let mut new_uncertainty = (T::TRY_ONE.unwrap() - kalman_gain) * state.uncertainty;
-  // adjust for process variance (normally done in a "predict" step
-  new_uncertainty = new_uncertainty + state.process_variance;
-  let est_diff =
-    if state.estimate > new_estimate { state.estimate - new_estimate }
-    else { new_estimate - state.estimate };
-  new_uncertainty = new_uncertainty + state.process_variance*est_diff;
-
-  KalmanState::new_fixed(new_estimate, new_uncertainty,
-                                state.measurement_variance, state.process_variance)
+  kalman_update(state, observation)
 }
 
 #[cfg(test)]
@@ -212,11 +420,11 @@ mod tests {
     assert_near_eq_fixed(
       kstate.estimate,
       TestType::from_num(max_iterations),
-      TestType::from_num( 4E-1),
+      TestType::from_num(0.75),
     );
     assert_near_eq_fixed(
       kstate.uncertainty,
-      TestType::from_num(0.002),
+      TestType::from_num(0.0016),
       TestType::from_num(1E-3),
     );
   }
@@ -245,7 +453,7 @@ mod tests {
     );
     assert_near_eq_fixed(
       kstate.uncertainty,
-      TestType::from_num(0.003),
+      TestType::from_num(0.0016),
       TestType::from_num(1E-3),
     );
   }
@@ -280,4 +488,284 @@ mod tests {
       TestType::from_num(1E-2),
     );
   }
+
+  #[test]
+  fn test_predict_update_f64() {
+    let mut kstate = KalmanState::new_float(0.0f64, 1.0, 1E-2, 1E-4);
+
+    // predict alone only grows the uncertainty
+    kstate.predict();
+    assert_near_eq(kstate.estimate, 0.0, 1E-9);
+    assert_near_eq(kstate.uncertainty, 1.0001, 1E-9);
+
+    for _i in 0..100 {
+      kstate.predict();
+      kstate.update(2.0);
+    }
+    println!("est: {} uncert: {}", kstate.estimate, kstate.uncertainty);
+    assert_near_eq(kstate.estimate, 2.0, 1E-4);
+    // steady state: p^2 + q*p - q*r = 0 (posterior p ~ 9.5E-4)
+    assert_near_eq(kstate.uncertainty, 9.5E-4, 1E-5);
+  }
+
+  #[test]
+  fn test_predict_update_i16f16() {
+    type TestType = I16F16;
+    let mut kstate = KalmanState::new_fixed(
+      TestType::from_num(10),
+      TestType::from_num(1),
+      TestType::from_num(1E-2),
+      TestType::from_num(1E-4),
+    );
+
+    for _i in 0..100 {
+      kstate.predict();
+      kstate.update(TestType::from_num(2));
+    }
+    println!("est: {} uncert: {}", kstate.estimate, kstate.uncertainty);
+    assert_near_eq_fixed(kstate.estimate, TestType::from_num(2), TestType::from_num(1E-3));
+    assert_near_eq_fixed(kstate.uncertainty, TestType::from_num(9.5E-4), TestType::from_num(1E-4));
+  }
+
+  #[test]
+  fn test_predict_dt_f64() {
+    let mut short_gap = KalmanState::new_float(0.0f64, 1E-3, 1E-2, 1E-2);
+    let mut long_gap = short_gap;
+
+    short_gap.predict_dt(0.01);
+    long_gap.predict_dt(5.0);
+    assert_near_eq(short_gap.uncertainty, 1.1E-3, 1E-9);
+    assert_near_eq(long_gap.uncertainty, 5.1E-2, 1E-9);
+
+    // after a long silence the new reading should dominate the estimate
+    short_gap.update(1.0);
+    long_gap.update(1.0);
+    assert!(long_gap.estimate > 0.8);
+    assert!(short_gap.estimate < 0.1);
+
+    // dt of one unit matches the plain predict step
+    let mut unit_dt = KalmanState::new_float(0.0f64, 1E-3, 1E-2, 1E-2);
+    let mut plain = unit_dt;
+    unit_dt.predict_dt(1.0);
+    plain.predict();
+    assert_near_eq(unit_dt.uncertainty, plain.uncertainty, 1E-12);
+  }
+
+  #[test]
+  fn test_predict_dt_u32f32() {
+    type TestType = U32F32;
+    let mut kstate = KalmanState::new_fixed(
+      TestType::from_num(100),
+      TestType::from_num(1E-3),
+      TestType::from_num(1E-2),
+      TestType::from_num(1E-2),
+    );
+
+    kstate.predict_fixed_dt(I16F16::from_num(5)).unwrap();
+    assert_near_eq_fixed(kstate.uncertainty, TestType::from_num(5.1E-2), TestType::from_num(1E-6));
+
+    kstate.update(TestType::from_num(101));
+    assert!(kstate.estimate > TestType::from_num(100.8));
+
+    // the same-type predict_dt agrees with a mixed-type duration
+    let mut same = KalmanState::new_fixed(
+      TestType::from_num(100),
+      TestType::from_num(1E-3),
+      TestType::from_num(1E-2),
+      TestType::from_num(1E-2),
+    );
+    let mut mixed = same;
+    same.predict_dt(TestType::from_num(5));
+    mixed.predict_fixed_dt(I16F16::from_num(-5)).unwrap();
+    assert_eq!(mixed.uncertainty, same.uncertainty);
+
+    // a duration that does not fit in the state type is reported
+    let mut narrow = KalmanState::new_fixed(
+      I16F16::from_num(0),
+      I16F16::from_num(1),
+      I16F16::from_num(1),
+      I16F16::from_num(1E-3),
+    );
+    assert_eq!(narrow.predict_fixed_dt(TestType::from_num(100000)), Err(KalmanError::Overflow));
+    assert_eq!(narrow.uncertainty, I16F16::from_num(1));
+    // the most negative duration still converts when its magnitude fits
+    narrow.predict_fixed_dt(I8F24::MIN).unwrap();
+    assert_eq!(narrow.uncertainty, I16F16::from_num(1) + I16F16::from_num(1E-3) * I16F16::from_num(128));
+  }
+
+  #[test]
+  fn test_update_with_variance_f64() {
+    let mut kstate = KalmanState::new_float(0.0f64, 1.0, 1.0, 0.0);
+
+    // a precise and an imprecise sensor disagree: the precise one should win
+    kstate.update_with_variance(10.0, 1E-4);
+    kstate.update_with_variance(20.0, 1.0);
+    println!("est: {} uncert: {}", kstate.estimate, kstate.uncertainty);
+    assert_near_eq(kstate.estimate, 10.0, 1E-2);
+
+    // equal variances reduce to the default update
+    let mut a = KalmanState::new_float(0.0f64, 1.0, 0.5, 0.0);
+    let mut b = a;
+    a.update(3.0);
+    b.update_with_variance(3.0, 0.5);
+    assert_near_eq(a.estimate, b.estimate, 1E-12);
+    assert_near_eq(a.uncertainty, b.uncertainty, 1E-12);
+  }
+
+  #[test]
+  fn test_update_with_variance_i16f16() {
+    type TestType = I16F16;
+    let mut kstate = KalmanState::new_fixed(
+      TestType::from_num(0),
+      TestType::from_num(1),
+      TestType::from_num(1),
+      TestType::from_num(0),
+    );
+
+    kstate.update_with_variance(TestType::from_num(10), TestType::from_num(1E-3));
+    kstate.update_with_variance(TestType::from_num(20), TestType::from_num(1));
+    println!("est: {} uncert: {}", kstate.estimate, kstate.uncertainty);
+    assert_near_eq_fixed(kstate.estimate, TestType::from_num(10), TestType::from_num(2E-2));
+  }
+
+  #[test]
+  fn test_float_and_fixed_agree() {
+    use fixed::types::I32F32;
+    let mut float_state = KalmanState::new_float(0.0f64, 1.0, 1E-2, 1E-3);
+    let mut fixed_state = KalmanState::new_fixed(
+      I32F32::from_num(0),
+      I32F32::from_num(1),
+      I32F32::from_num(1E-2),
+      I32F32::from_num(1E-3),
+    );
+
+    for i in 1..=200 {
+      let observation = (i % 7) as f64 - 3.0;
+      float_state = kalman_update_float(&float_state, observation);
+      fixed_state = kalman_update_fixed(&fixed_state, I32F32::from_num(observation));
+      float_state.predict();
+      fixed_state.predict();
+    }
+    println!("float: {} fixed: {}", float_state.estimate, fixed_state.estimate);
+    assert_near_eq(float_state.estimate, fixed_state.estimate.to_num(), 1E-4);
+    assert_near_eq(float_state.uncertainty, fixed_state.uncertainty.to_num(), 1E-4);
+  }
+
+  #[test]
+  fn test_checked_update_overflow_i8f24() {
+    type TestType = I8F24;
+    let mut kstate = KalmanState::new_fixed(
+      TestType::from_num(-100),
+      TestType::from_num(1),
+      TestType::from_num(1E-3),
+      TestType::from_num(1E-3),
+    );
+
+    // residual of 200 does not fit in I8F24
+    let before = kstate;
+    assert_eq!(kstate.checked_update(TestType::from_num(100)), Err(KalmanError::Overflow));
+    assert_eq!(kstate.estimate, before.estimate);
+    assert_eq!(kstate.uncertainty, before.uncertainty);
+
+    assert_eq!(kstate.checked_update(TestType::from_num(-90)), Ok(()));
+    assert_near_eq_fixed(kstate.estimate, TestType::from_num(-90), TestType::from_num(2E-2));
+
+    kstate.uncertainty = TestType::MAX;
+    assert_eq!(kstate.checked_predict(), Err(KalmanError::Overflow));
+  }
+
+  #[test]
+  fn test_saturating_update_i8f24() {
+    type TestType = I8F24;
+    let mut kstate = KalmanState::new_fixed(
+      TestType::from_num(-100),
+      TestType::from_num(1),
+      TestType::from_num(1E-3),
+      TestType::from_num(1E-3),
+    );
+
+    kstate.saturating_update(TestType::from_num(100));
+    println!("est: {} uncert: {}", kstate.estimate, kstate.uncertainty);
+    // the residual saturates, but the estimate still moves towards the observation
+    assert!(kstate.estimate > TestType::from_num(0));
+    for _i in 0..10 {
+      kstate.saturating_predict();
+      kstate.saturating_update(TestType::from_num(100));
+    }
+    assert_near_eq_fixed(kstate.estimate, TestType::from_num(100), TestType::from_num(1E-2));
+
+    kstate.uncertainty = TestType::MAX;
+    kstate.saturating_predict();
+    assert_eq!(kstate.uncertainty, TestType::MAX);
+  }
+
+  #[test]
+  fn test_update_report_f64() {
+    let kstate = KalmanState::new(1.0f64, 3.0, 1.0, 0.0);
+    let (new_state, report) = kalman_update_with_report(&kstate, 5.0);
+
+    assert_near_eq(report.innovation, 4.0, 1E-12);
+    assert_near_eq(report.innovation_variance, 4.0, 1E-12);
+    assert_near_eq(report.gain, 0.75, 1E-12);
+    assert_near_eq(new_state.estimate, 4.0, 1E-12);
+    assert_near_eq(report.residual, 1.0, 1E-12);
+    assert_near_eq(report.normalized_innovation_squared(), 4.0, 1E-12);
+  }
+
+  #[test]
+  fn test_update_report_u32f32() {
+    type TestType = U32F32;
+    let mut kstate = KalmanState::new_fixed(
+      TestType::from_num(10),
+      TestType::from_num(1),
+      TestType::from_num(1),
+      TestType::from_num(0),
+    );
+
+    // unsigned types report the magnitude of a negative innovation
+    let report = kstate.update(TestType::from_num(6));
+    assert_eq!(report.innovation, TestType::from_num(4));
+    assert_eq!(report.gain, TestType::from_num(0.5));
+    assert_eq!(report.residual, TestType::from_num(2));
+    assert_eq!(kstate.estimate, TestType::from_num(8));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_round_trip_f64() {
+    let kstate = KalmanState::new(1.5f64, 0.25, 0.1, 1E-3);
+    let json = serde_json::to_string(&kstate).unwrap();
+    println!("json: {}", json);
+    let restored: KalmanState<f64> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.estimate, kstate.estimate);
+    assert_eq!(restored.uncertainty, kstate.uncertainty);
+    assert_eq!(restored.measurement_variance, kstate.measurement_variance);
+    assert_eq!(restored.process_variance, kstate.process_variance);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_round_trip_i16f16() {
+    type TestType = I16F16;
+    let kstate = KalmanState::new_fixed(
+      TestType::from_num(-3.25),
+      TestType::from_num(1),
+      TestType::from_num(0.5),
+      TestType::from_num(0.01),
+    );
+    let json = serde_json::to_string(&kstate).unwrap();
+    let restored: KalmanState<TestType> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.estimate, kstate.estimate);
+    assert_eq!(restored.measurement_variance, kstate.measurement_variance);
+    assert_eq!(restored.process_variance, kstate.process_variance);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_rejects_negative_variance() {
+    let json = r#"{"estimate":1.0,"uncertainty":0.5,"measurement_variance":-0.1,"process_variance":0.01}"#;
+    assert!(serde_json::from_str::<KalmanState<f64>>(json).is_err());
+    let json = r#"{"estimate":1.0,"uncertainty":-0.5,"measurement_variance":0.1,"process_variance":0.01}"#;
+    assert!(serde_json::from_str::<KalmanState<f64>>(json).is_err());
+  }
 }
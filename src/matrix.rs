@@ -0,0 +1,270 @@
+use core::ops::{Add, Index, IndexMut, Mul, Sub};
+
+use crate::Scalar;
+#[cfg(feature = "serde")]
+use crate::serde_array::{DeArray, SerArray};
+
+/// A small stack-allocated matrix with `R` rows and `C` columns,
+/// stored in row-major order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix<T, const R: usize, const C: usize>(pub [[T; C]; R]);
+
+/// A column vector with `N` rows
+pub type Vector<T, const N: usize> = Matrix<T, N, 1>;
+
+impl<T, const R: usize, const C: usize> Matrix<T, R, C>
+  where T: Scalar
+{
+  pub fn new(rows: [[T; C]; R]) -> Self {
+    Matrix(rows)
+  }
+
+  pub fn zeros() -> Self {
+    Matrix([[T::zero(); C]; R])
+  }
+
+  pub fn transpose(&self) -> Matrix<T, C, R> {
+    let mut out = Matrix::<T, C, R>::zeros();
+    for r in 0..R {
+      for c in 0..C {
+        out.0[c][r] = self.0[r][c];
+      }
+    }
+    out
+  }
+
+  /// Multiply every element by `factor`
+  pub fn scale(&self, factor: T) -> Self {
+    let mut out = *self;
+    for row in out.0.iter_mut() {
+      for val in row.iter_mut() {
+        *val = *val * factor;
+      }
+    }
+    out
+  }
+}
+
+impl<T, const N: usize> Matrix<T, N, N>
+  where T: Scalar
+{
+  pub fn identity() -> Self {
+    let mut out = Self::zeros();
+    for i in 0..N {
+      out.0[i][i] = T::one();
+    }
+    out
+  }
+
+  /// Invert a square matrix by Gauss-Jordan elimination with partial pivoting.
+  /// Returns `None` if the matrix is singular.
+  pub fn inverse(&self) -> Option<Self> {
+    let mut work = *self;
+    let mut inv = Self::identity();
+
+    for col in 0..N {
+      // choose the largest remaining pivot in this column
+      let mut pivot = col;
+      for row in (col + 1)..N {
+        if work.0[row][col].abs() > work.0[pivot][col].abs() {
+          pivot = row;
+        }
+      }
+      if work.0[pivot][col] == T::zero() {
+        return None;
+      }
+      work.0.swap(col, pivot);
+      inv.0.swap(col, pivot);
+
+      let pivot_val = work.0[col][col];
+      for c in 0..N {
+        work.0[col][c] = work.0[col][c] / pivot_val;
+        inv.0[col][c] = inv.0[col][c] / pivot_val;
+      }
+
+      for row in 0..N {
+        if row == col {
+          continue;
+        }
+        let factor = work.0[row][col];
+        if factor == T::zero() {
+          continue;
+        }
+        for c in 0..N {
+          work.0[row][c] = work.0[row][c] - factor * work.0[col][c];
+          inv.0[row][c] = inv.0[row][c] - factor * inv.0[col][c];
+        }
+      }
+    }
+    Some(inv)
+  }
+
+  /// Cholesky decomposition of a symmetric positive semi-definite matrix:
+  /// returns the lower triangular `L` such that `L L' = self`,
+  /// or `None` if the matrix is not positive semi-definite.
+  pub fn cholesky(&self) -> Option<Self> {
+    let mut lower = Self::zeros();
+    for i in 0..N {
+      for j in 0..=i {
+        let mut sum = self.0[i][j];
+        for k in 0..j {
+          sum = sum - lower.0[i][k] * lower.0[j][k];
+        }
+        if i == j {
+          if sum < T::zero() {
+            return None;
+          }
+          lower.0[i][i] = sum.sqrt();
+        }
+        else if lower.0[j][j] != T::zero() {
+          lower.0[i][j] = sum / lower.0[j][j];
+        }
+      }
+    }
+    Some(lower)
+  }
+}
+
+impl<T, const R: usize, const C: usize> Index<(usize, usize)> for Matrix<T, R, C> {
+  type Output = T;
+
+  fn index(&self, (row, col): (usize, usize)) -> &T {
+    &self.0[row][col]
+  }
+}
+
+impl<T, const R: usize, const C: usize> IndexMut<(usize, usize)> for Matrix<T, R, C> {
+  fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+    &mut self.0[row][col]
+  }
+}
+
+impl<T, const R: usize, const C: usize> Add for Matrix<T, R, C>
+  where T: Scalar
+{
+  type Output = Self;
+
+  fn add(self, rhs: Self) -> Self {
+    let mut out = self;
+    for r in 0..R {
+      for c in 0..C {
+        out.0[r][c] = self.0[r][c] + rhs.0[r][c];
+      }
+    }
+    out
+  }
+}
+
+impl<T, const R: usize, const C: usize> Sub for Matrix<T, R, C>
+  where T: Scalar
+{
+  type Output = Self;
+
+  fn sub(self, rhs: Self) -> Self {
+    let mut out = self;
+    for r in 0..R {
+      for c in 0..C {
+        out.0[r][c] = self.0[r][c] - rhs.0[r][c];
+      }
+    }
+    out
+  }
+}
+
+impl<T, const R: usize, const C: usize, const K: usize> Mul<Matrix<T, C, K>> for Matrix<T, R, C>
+  where T: Scalar
+{
+  type Output = Matrix<T, R, K>;
+
+  fn mul(self, rhs: Matrix<T, C, K>) -> Matrix<T, R, K> {
+    let mut out = Matrix::<T, R, K>::zeros();
+    for r in 0..R {
+      for k in 0..K {
+        let mut sum = T::zero();
+        for c in 0..C {
+          sum = sum + self.0[r][c] * rhs.0[c][k];
+        }
+        out.0[r][k] = sum;
+      }
+    }
+    out
+  }
+}
+
+/// Serialized as a tuple of row tuples
+#[cfg(feature = "serde")]
+impl<T, const R: usize, const C: usize> serde::Serialize for Matrix<T, R, C>
+  where T: serde::Serialize
+{
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeTuple;
+    let mut rows = serializer.serialize_tuple(R)?;
+    for row in self.0.iter() {
+      rows.serialize_element(&SerArray(row))?;
+    }
+    rows.end()
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T, const R: usize, const C: usize> serde::Deserialize<'de> for Matrix<T, R, C>
+  where T: serde::Deserialize<'de>
+{
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let rows: [DeArray<T, C>; R] = crate::serde_array::deserialize(deserializer)?;
+    Ok(Matrix(rows.map(|row| row.0)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I16F16;
+
+  #[test]
+  fn test_inverse_f64() {
+    let m = Matrix::new([[4.0f64, 7.0], [2.0, 6.0]]);
+    let inv = m.inverse().unwrap();
+    let prod = m * inv;
+    for r in 0..2 {
+      for c in 0..2 {
+        let expected = if r == c { 1.0 } else { 0.0 };
+        assert!((prod[(r, c)] - expected).abs() < 1E-12);
+      }
+    }
+    assert!(Matrix::new([[1.0f64, 2.0], [2.0, 4.0]]).inverse().is_none());
+  }
+
+  #[test]
+  fn test_cholesky_f64() {
+    let m = Matrix::new([[4.0f64, 2.0], [2.0, 10.0]]);
+    let lower = m.cholesky().unwrap();
+    assert_eq!(lower, Matrix::new([[2.0, 0.0], [1.0, 3.0]]));
+    assert_eq!(lower * lower.transpose(), m);
+    assert!(Matrix::new([[-1.0f64, 0.0], [0.0, 1.0]]).cholesky().is_none());
+  }
+
+  #[test]
+  fn test_inverse_i16f16() {
+    type TestType = I16F16;
+    let m = Matrix::new([
+      [TestType::from_num(0), TestType::from_num(2)],
+      [TestType::from_num(4), TestType::from_num(0)],
+    ]);
+    let inv = m.inverse().unwrap();
+    assert_eq!(inv[(0, 1)], TestType::from_num(0.25));
+    assert_eq!(inv[(1, 0)], TestType::from_num(0.5));
+    assert_eq!(m.transpose()[(0, 1)], TestType::from_num(4));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_rows() {
+    let m = Matrix::new([[1.0f64, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+    let json = serde_json::to_string(&m).unwrap();
+    assert_eq!(json, "[[1.0,2.0,3.0],[4.0,5.0,6.0]]");
+    let restored: Matrix<f64, 2, 3> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, m);
+    assert!(serde_json::from_str::<Matrix<f64, 2, 3>>("[[1.0,2.0],[3.0,4.0]]").is_err());
+  }
+}
@@ -0,0 +1,266 @@
+use crate::{KalmanError, KalmanState, Scalar};
+
+/// One retained measurement and the filter state just after applying it
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+struct HistoryEntry<T> {
+  time: T,
+  observation: T,
+  measurement_variance: T,
+  state: KalmanState<T>,
+}
+
+/// A single-variable filter that accepts measurements out of time order.
+/// It keeps the last `DEPTH` measurements, sorted by timestamp, together with
+/// the filter state after each one. A late measurement is inserted at its true
+/// time and the later measurements are replayed to bring the estimate back to
+/// the present. Process noise is scaled by the elapsed time between
+/// measurements, as in `KalmanState::predict_dt`. No allocation is performed.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "OutOfSequenceData<T, DEPTH>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct OutOfSequenceFilter<T, const DEPTH: usize> {
+  anchor: KalmanState<T>,  // state before the oldest retained measurement
+  anchor_time: T,
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+  history: [HistoryEntry<T>; DEPTH],
+  len: usize,
+}
+
+/// Unvalidated `OutOfSequenceFilter` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(deserialize = "T: Scalar + serde::Deserialize<'de>"))]
+struct OutOfSequenceData<T, const DEPTH: usize> {
+  anchor: KalmanState<T>,
+  anchor_time: T,
+  #[serde(with = "crate::serde_array")]
+  history: [HistoryEntry<T>; DEPTH],
+  len: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<T, const DEPTH: usize> TryFrom<OutOfSequenceData<T, DEPTH>> for OutOfSequenceFilter<T, DEPTH>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: OutOfSequenceData<T, DEPTH>) -> Result<Self, Self::Error> {
+    if data.len > DEPTH {
+      return Err("history length exceeds depth");
+    }
+    let mut time = data.anchor_time;
+    for entry in data.history[..data.len].iter() {
+      if entry.time < time {
+        return Err("history is not in time order");
+      }
+      time = entry.time;
+    }
+    Ok(OutOfSequenceFilter {
+      anchor: data.anchor,
+      anchor_time: data.anchor_time,
+      history: data.history,
+      len: data.len,
+    })
+  }
+}
+
+impl<T, const DEPTH: usize> OutOfSequenceFilter<T, DEPTH>
+  where T: Scalar
+{
+  /// Create a filter whose initial state is valid at `time`
+  pub fn new(initial: KalmanState<T>, time: T) -> Self {
+    let empty = HistoryEntry {
+      time,
+      observation: initial.estimate,
+      measurement_variance: initial.measurement_variance,
+      state: initial,
+    };
+    OutOfSequenceFilter {
+      anchor: initial,
+      anchor_time: time,
+      history: [empty; DEPTH],
+      len: 0,
+    }
+  }
+
+  /// The filter state after the most recent (by timestamp) measurement
+  pub fn state(&self) -> KalmanState<T> {
+    if self.len == 0 { self.anchor } else { self.history[self.len - 1].state }
+  }
+
+  /// The timestamp of the most recent measurement
+  pub fn latest_time(&self) -> T {
+    if self.len == 0 { self.anchor_time } else { self.history[self.len - 1].time }
+  }
+
+  /// The oldest timestamp at which a measurement can still be incorporated
+  pub fn earliest_time(&self) -> T {
+    self.anchor_time
+  }
+
+  /// The current state propagated forward to `time` without an observation
+  pub fn predict_to(&self, time: T) -> KalmanState<T> {
+    let mut state = self.state();
+    let latest = self.latest_time();
+    if time > latest {
+      state.predict_dt(time - latest);
+    }
+    state
+  }
+
+  /// Incorporate a measurement taken at `time`, which may be earlier than
+  /// measurements already received. Fails if `time` is older than the
+  /// retained history, in which case the filter is left unchanged.
+  pub fn update(&mut self, time: T, observation: T) -> Result<(), KalmanError> {
+    let measurement_variance = self.anchor.measurement_variance;
+    self.update_with_variance(time, observation, measurement_variance)
+  }
+
+  /// As `update`, using the given measurement variance for this observation
+  pub fn update_with_variance(&mut self, time: T, observation: T, measurement_variance: T)
+    -> Result<(), KalmanError>
+  {
+    if time < self.anchor_time {
+      return Err(KalmanError::MeasurementTooOld);
+    }
+    let entry = HistoryEntry {
+      time,
+      observation,
+      measurement_variance,
+      state: self.anchor,
+    };
+
+    // insert after any measurements at the same or earlier time
+    let mut pos = self.history[..self.len].iter().take_while(|e| e.time <= time).count();
+
+    if self.len == DEPTH {
+      if pos == 0 {
+        // the new measurement is the oldest: fold it straight into the anchor
+        self.anchor = Self::apply(&self.anchor, self.anchor_time, &entry);
+        self.anchor_time = time;
+        self.replay(0);
+        return Ok(());
+      }
+      // retire the oldest measurement into the anchor to make room
+      self.anchor = self.history[0].state;
+      self.anchor_time = self.history[0].time;
+      self.history.copy_within(1..DEPTH, 0);
+      self.len -= 1;
+      pos -= 1;
+    }
+
+    self.history.copy_within(pos..self.len, pos + 1);
+    self.history[pos] = entry;
+    self.len += 1;
+    self.replay(pos);
+    Ok(())
+  }
+
+  /// Apply one measurement to `state`, which is valid at `time`
+  fn apply(state: &KalmanState<T>, time: T, entry: &HistoryEntry<T>) -> KalmanState<T> {
+    let mut next = *state;
+    next.predict_dt(entry.time - time);
+    next.update_with_variance(entry.observation, entry.measurement_variance);
+    next
+  }
+
+  /// Recompute the stored states from history index `from` onwards
+  fn replay(&mut self, from: usize) {
+    let (mut state, mut time) =
+      if from == 0 { (self.anchor, self.anchor_time) }
+      else { (self.history[from - 1].state, self.history[from - 1].time) };
+    for entry in self.history[from..self.len].iter_mut() {
+      state = Self::apply(&state, time, entry);
+      time = entry.time;
+      entry.state = state;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+
+  #[test]
+  fn test_late_measurement_matches_in_order_f64() {
+    let initial = KalmanState::new(0.0f64, 1.0, 0.1, 1E-2);
+    let readings = [(1.0, 1.1), (2.0, 0.9), (3.5, 1.3), (4.0, 1.0), (6.0, 1.2)];
+
+    let mut in_order: OutOfSequenceFilter<f64, 8> = OutOfSequenceFilter::new(initial, 0.0);
+    for &(time, observation) in readings.iter() {
+      in_order.update(time, observation).unwrap();
+    }
+
+    // the reading at t=2 arrives last
+    let mut late: OutOfSequenceFilter<f64, 8> = OutOfSequenceFilter::new(initial, 0.0);
+    for &(time, observation) in readings.iter().filter(|r| r.0 != 2.0) {
+      late.update(time, observation).unwrap();
+    }
+    late.update(2.0, 0.9).unwrap();
+
+    println!("in order: {:?} late: {:?}", in_order.state(), late.state());
+    assert!((in_order.state().estimate - late.state().estimate).abs() < 1E-12);
+    assert!((in_order.state().uncertainty - late.state().uncertainty).abs() < 1E-12);
+    assert_eq!(late.latest_time(), 6.0);
+  }
+
+  #[test]
+  fn test_bounded_history_f64() {
+    let initial = KalmanState::new(0.0f64, 1.0, 0.1, 1E-2);
+    let mut filter: OutOfSequenceFilter<f64, 2> = OutOfSequenceFilter::new(initial, 0.0);
+    let mut reference = initial;
+    for time in 1..=5 {
+      filter.update(time as f64, 1.0).unwrap();
+      reference.predict_dt(1.0);
+      reference.update(1.0);
+    }
+    // only the last two measurements are retained
+    assert_eq!(filter.earliest_time(), 3.0);
+    assert_eq!(filter.update(2.5, 1.0), Err(KalmanError::MeasurementTooOld));
+    assert!((filter.state().estimate - reference.estimate).abs() < 1E-12);
+
+    // a measurement older than everything retained, but not the anchor, is still accepted
+    assert_eq!(filter.update(3.0, 2.0), Ok(()));
+    assert!(filter.state().estimate > reference.estimate);
+  }
+
+  #[test]
+  fn test_out_of_order_i32f32() {
+    type TestType = I32F32;
+    let initial = KalmanState::new(
+      TestType::from_num(10),
+      TestType::from_num(1),
+      TestType::from_num(0.5),
+      TestType::from_num(0.01),
+    );
+    let mut filter: OutOfSequenceFilter<TestType, 4> = OutOfSequenceFilter::new(initial, TestType::from_num(0));
+    filter.update(TestType::from_num(3), TestType::from_num(12)).unwrap();
+    filter.update(TestType::from_num(1), TestType::from_num(11)).unwrap();
+    filter.update(TestType::from_num(2), TestType::from_num(11.5)).unwrap();
+    assert_eq!(filter.latest_time(), TestType::from_num(3));
+
+    let predicted = filter.predict_to(TestType::from_num(5));
+    assert_eq!(predicted.estimate, filter.state().estimate);
+    assert!(predicted.uncertainty > filter.state().uncertainty);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_validates_history_f64() {
+    let initial = KalmanState::new(0.0f64, 1.0, 0.1, 1E-2);
+    let mut filter: OutOfSequenceFilter<f64, 2> = OutOfSequenceFilter::new(initial, 0.0);
+    filter.update(1.0, 1.0).unwrap();
+    let json = serde_json::to_string(&filter).unwrap();
+    let restored: OutOfSequenceFilter<f64, 2> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.latest_time(), 1.0);
+
+    // a corrupt length fails to load rather than panicking later
+    let corrupt = json.replace(r#""len":1"#, r#""len":5"#);
+    assert!(serde_json::from_str::<OutOfSequenceFilter<f64, 2>>(&corrupt).is_err());
+  }
+}
@@ -0,0 +1,193 @@
+use num_traits::Signed;
+
+use crate::Scalar;
+
+/// Holds state for a two-state constant-velocity Kalman filter:
+/// a value and its rate of change (for example clock offset and drift rate).
+/// Unlike the scalar `KalmanState`, this tracks a ramp without steady-state lag.
+/// Requires a signed Scalar type, since the covariance may be negative.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "KalmanRateStateData<T>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct KalmanRateState<T> {
+  /// Estimated value of the variable
+  pub estimate: T,
+  /// Estimated rate of change of the variable, per unit of time
+  pub rate: T,
+  /// Covariance of the (estimate, rate) pair
+  pub covariance: [[T; 2]; 2],
+  measurement_variance: T,  // Uncertainty in the measurement itself
+  process_variance: T,      // Spectral density of the (white noise) rate acceleration
+}
+
+/// Unvalidated `KalmanRateState` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct KalmanRateStateData<T> {
+  estimate: T,
+  rate: T,
+  covariance: [[T; 2]; 2],
+  measurement_variance: T,
+  process_variance: T,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<KalmanRateStateData<T>> for KalmanRateState<T>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: KalmanRateStateData<T>) -> Result<Self, Self::Error> {
+    let zero = T::zero();
+    if data.covariance[0][0] < zero || data.covariance[1][1] < zero
+      || data.measurement_variance < zero || data.process_variance < zero
+    {
+      return Err("negative uncertainty or variance");
+    }
+    Ok(KalmanRateState {
+      estimate: data.estimate,
+      rate: data.rate,
+      covariance: data.covariance,
+      measurement_variance: data.measurement_variance,
+      process_variance: data.process_variance,
+    })
+  }
+}
+
+impl<T> KalmanRateState<T>
+  where T: Copy
+{
+  /// Uncertainty (variance) of the value estimate
+  pub fn uncertainty(&self) -> T {
+    self.covariance[0][0]
+  }
+}
+
+impl<T> KalmanRateState<T>
+  where T: Scalar + Signed
+{
+  pub fn new(
+    estimate: T,
+    rate: T,
+    uncertainty: T,
+    rate_uncertainty: T,
+    measurement_variance: T,
+    process_variance: T) -> KalmanRateState<T>
+  {
+    KalmanRateState {
+      estimate,
+      rate,
+      covariance: [
+        [Scalar::abs(uncertainty), T::zero()],
+        [T::zero(), Scalar::abs(rate_uncertainty)],
+      ],
+      measurement_variance: Scalar::abs(measurement_variance),
+      process_variance: Scalar::abs(process_variance),
+    }
+  }
+
+  /// Predict step:
+  /// propagate the value forward by `dt` at the estimated rate,
+  /// and grow the covariance by the integrated process noise.
+  pub fn predict(&mut self, dt: T) {
+    let dt = Scalar::abs(dt);
+    let two = T::one() + T::one();
+    let three = two + T::one();
+    let q_dt = self.process_variance * dt;
+    let q_dt2 = q_dt * dt;
+    let [[p00, p01], [_, p11]] = self.covariance;
+
+    self.estimate = self.estimate + self.rate * dt;
+
+    let new_p00 = p00 + dt * (p01 + p01) + dt * dt * p11 + q_dt2 * dt / three;
+    let new_p01 = p01 + dt * p11 + q_dt2 / two;
+    let new_p11 = p11 + q_dt;
+    self.covariance = [[new_p00, new_p01], [new_p01, new_p11]];
+  }
+
+  /// Update step:
+  /// incorporate a single observation of the value.
+  pub fn update(&mut self, observation: T) {
+    self.update_with_variance(observation, self.measurement_variance);
+  }
+
+  /// Update step, using the given measurement variance
+  /// for this observation instead of the one configured at construction.
+  pub fn update_with_variance(&mut self, observation: T, measurement_variance: T) {
+    let [[p00, p01], [_, p11]] = self.covariance;
+    let innovation_variance = p00 + Scalar::abs(measurement_variance);
+    let gain0 = p00 / innovation_variance;
+    let gain1 = p01 / innovation_variance;
+    let residual = observation - self.estimate;
+
+    self.estimate = self.estimate + gain0 * residual;
+    self.rate = self.rate + gain1 * residual;
+
+    let new_p00 = (T::one() - gain0) * p00;
+    let new_p01 = (T::one() - gain0) * p01;
+    let new_p11 = p11 - gain1 * p01;
+    self.covariance = [[new_p00, new_p01], [new_p01, new_p11]];
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::KalmanState;
+  use fixed::types::I32F32;
+
+  #[test]
+  fn test_ramp_without_lag_f64() {
+    let mut rstate = KalmanRateState::new(0.0f64, 0.0, 1.0, 1.0, 1E-2, 1E-6);
+    let mut kstate = KalmanState::new(0.0f64, 1.0, 1E-2, 1E-6);
+
+    const MAX_ITERATIONS: usize = 500;
+    for i in 1..=MAX_ITERATIONS {
+      rstate.predict(1.0);
+      rstate.update(i as f64);
+      kstate.predict();
+      kstate.update(i as f64);
+    }
+    println!("rate est: {} rate: {} scalar est: {}", rstate.estimate, rstate.rate, kstate.estimate);
+    assert!((rstate.estimate - MAX_ITERATIONS as f64).abs() < 1E-3);
+    assert!((rstate.rate - 1.0).abs() < 1E-3);
+    // the scalar random-walk model lags well behind the ramp
+    assert!((MAX_ITERATIONS as f64 - kstate.estimate) > 1.0);
+  }
+
+  #[test]
+  fn test_ramp_without_lag_i32f32() {
+    type TestType = I32F32;
+    let mut rstate = KalmanRateState::new(
+      TestType::from_num(100),
+      TestType::from_num(0),
+      TestType::from_num(1),
+      TestType::from_num(1),
+      TestType::from_num(1E-2),
+      TestType::from_num(1E-6),
+    );
+
+    let dt = TestType::from_num(0.5);
+    for i in 1..=500 {
+      rstate.predict(dt);
+      rstate.update(TestType::from_num(100) - dt * TestType::from_num(i));
+    }
+    println!("est: {} rate: {}", rstate.estimate, rstate.rate);
+    assert!((rstate.estimate - TestType::from_num(-150)).abs() < TestType::from_num(1E-2));
+    assert!((rstate.rate - TestType::from_num(-1)).abs() < TestType::from_num(1E-2));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_rejects_negative_variance() {
+    let rstate = KalmanRateState::new(1.0f64, 0.5, 1.0, 0.1, 1E-2, 1E-4);
+    let json = serde_json::to_string(&rstate).unwrap();
+    assert!(serde_json::from_str::<KalmanRateState<f64>>(&json).is_ok());
+    let corrupt = json.replace(r#""measurement_variance":0.01"#, r#""measurement_variance":-0.01"#);
+    assert!(serde_json::from_str::<KalmanRateState<f64>>(&corrupt).is_err());
+    let corrupt = json.replace(r#""covariance":[[1.0"#, r#""covariance":[[-1.0"#);
+    assert!(serde_json::from_str::<KalmanRateState<f64>>(&corrupt).is_err());
+  }
+}
@@ -0,0 +1,52 @@
+use crate::Scalar;
+
+/// Diagnostic details of a single update step,
+/// useful for telemetry and for detecting a misbehaving sensor.
+/// For unsigned types, which cannot represent a negative value,
+/// `innovation` and `residual` hold the magnitude of the difference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpdateReport<T> {
+  /// Observation minus the prior (pre-update) estimate
+  pub innovation: T,
+  /// Expected variance of the innovation: prior uncertainty plus measurement variance
+  pub innovation_variance: T,
+  /// Kalman gain applied to the innovation
+  pub gain: T,
+  /// Observation minus the posterior (post-update) estimate
+  pub residual: T,
+}
+
+impl<T> UpdateReport<T>
+  where T: Scalar
+{
+  /// Normalized innovation squared (NIS): innovation^2 / innovation_variance.
+  /// For a well-tuned filter this averages to one; a sustained
+  /// larger value suggests the sensor (or the model) has gone bad.
+  /// Returns zero when the innovation variance is zero.
+  pub fn normalized_innovation_squared(&self) -> T {
+    if self.innovation_variance == T::zero() {
+      return T::zero();
+    }
+    let scaled = self.innovation / self.innovation_variance.sqrt();
+    scaled * scaled
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+
+  #[test]
+  fn test_nis_zero_variance_i32f32() {
+    type TestType = I32F32;
+    let report = UpdateReport {
+      innovation: TestType::from_num(2),
+      innovation_variance: TestType::from_num(0),
+      gain: TestType::from_num(1),
+      residual: TestType::from_num(0),
+    };
+    assert_eq!(report.normalized_innovation_squared(), TestType::from_num(0));
+  }
+}
@@ -0,0 +1,124 @@
+use core::fmt::Debug;
+use num_traits::Num;
+
+use fixed::traits::Fixed;
+use fixed::types::extra::{
+  IsLessOrEqual, LeEqU128, LeEqU16, LeEqU32, LeEqU64, LeEqU8, True,
+  U126, U127, U14, U15, U30, U31, U6, U62, U63, U7,
+};
+use fixed::{
+  FixedI128, FixedI16, FixedI32, FixedI64, FixedI8,
+  FixedU128, FixedU16, FixedU32, FixedU64, FixedU8,
+};
+
+/// Numeric operations required by the filters in this crate.
+/// Implemented for `f32`, `f64` and every `fixed` type that can represent one,
+/// so that a single generic code path serves both Float and Fixed backends.
+pub trait Scalar: Num + Copy + PartialOrd + Debug {
+  /// Whether this type can represent negative values
+  const SIGNED: bool;
+
+  /// Absolute value (identity for unsigned types)
+  fn abs(self) -> Self;
+
+  /// Square root of a non-negative value
+  fn sqrt(self) -> Self;
+
+  /// Convert from an `f64` constant, rounding to the nearest representable value
+  fn from_f64(value: f64) -> Self;
+
+  /// Convert to `f64`
+  fn to_f64(self) -> f64;
+
+  /// Absolute difference between two values, safe for unsigned types
+  fn abs_diff(self, other: Self) -> Self {
+    if self > other { self - other } else { other - self }
+  }
+}
+
+/// Move `from` towards `to` by the fraction `weight` of the difference.
+/// Computes `from + weight * (to - from)` without forming a negative
+/// intermediate, so it is safe for unsigned types.
+pub(crate) fn blend<T: Scalar>(from: T, to: T, weight: T) -> T {
+  if to >= from {
+    from + weight * (to - from)
+  }
+  else {
+    from - weight * (from - to)
+  }
+}
+
+/// Compute `a - b`, or its magnitude for unsigned types
+/// which cannot represent a negative difference.
+pub(crate) fn signed_diff<T: Scalar>(a: T, b: T) -> T {
+  if T::SIGNED { a - b } else { a.abs_diff(b) }
+}
+
+macro_rules! impl_scalar_float {
+  ($($Float:ty),*) => {
+    $(
+      impl Scalar for $Float {
+        const SIGNED: bool = true;
+
+        fn abs(self) -> Self {
+          num_traits::Float::abs(self)
+        }
+
+        fn sqrt(self) -> Self {
+          num_traits::Float::sqrt(self)
+        }
+
+        fn from_f64(value: f64) -> Self {
+          value as $Float
+        }
+
+        fn to_f64(self) -> f64 {
+          self as f64
+        }
+      }
+    )*
+  };
+}
+
+impl_scalar_float! { f32, f64 }
+
+macro_rules! impl_scalar_fixed {
+  ($($Fixed:ident, $LeEqU:ident, $OneMaxFrac:ident, $signed:expr;)*) => {
+    $(
+      impl<Frac> Scalar for $Fixed<Frac>
+        where Frac: $LeEqU + IsLessOrEqual<$OneMaxFrac, Output = True>
+      {
+        const SIGNED: bool = $signed;
+
+        fn abs(self) -> Self {
+          if self < Self::ZERO { Self::ZERO - self } else { self }
+        }
+
+        fn sqrt(self) -> Self {
+          <Self as Fixed>::sqrt(self)
+        }
+
+        fn from_f64(value: f64) -> Self {
+          Self::saturating_from_num(value)
+        }
+
+        fn to_f64(self) -> f64 {
+          self.to_num()
+        }
+      }
+    )*
+  };
+}
+
+impl_scalar_fixed! {
+  FixedI8, LeEqU8, U6, true;
+  FixedI16, LeEqU16, U14, true;
+  FixedI32, LeEqU32, U30, true;
+  FixedI64, LeEqU64, U62, true;
+  FixedI128, LeEqU128, U126, true;
+  FixedU8, LeEqU8, U7, false;
+  FixedU16, LeEqU16, U15, false;
+  FixedU32, LeEqU32, U31, false;
+  FixedU64, LeEqU64, U63, false;
+  FixedU128, LeEqU128, U127, false;
+}
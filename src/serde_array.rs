@@ -0,0 +1,83 @@
+//! Serde support for arrays of any const-generic length, which serde
+//! itself only provides for lengths up to 32. Use on a field with
+//! `#[serde(with = "crate::serde_array")]`.
+
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeTuple, Serializer};
+
+pub(crate) fn serialize<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+  where S: Serializer, T: Serialize
+{
+  let mut tuple = serializer.serialize_tuple(N)?;
+  for element in array {
+    tuple.serialize_element(element)?;
+  }
+  tuple.end()
+}
+
+pub(crate) fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+  where D: Deserializer<'de>, T: Deserialize<'de>
+{
+  deserializer.deserialize_tuple(N, ArrayVisitor(PhantomData))
+}
+
+/// Serializes a borrowed array as a tuple
+pub(crate) struct SerArray<'a, T, const N: usize>(pub &'a [T; N]);
+
+impl<T, const N: usize> Serialize for SerArray<'_, T, N>
+  where T: Serialize
+{
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize(self.0, serializer)
+  }
+}
+
+/// Deserializes an array from a tuple
+pub(crate) struct DeArray<T, const N: usize>(pub [T; N]);
+
+impl<'de, T, const N: usize> Deserialize<'de> for DeArray<T, N>
+  where T: Deserialize<'de>
+{
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserialize(deserializer).map(DeArray)
+  }
+}
+
+struct ArrayVisitor<T, const N: usize>(PhantomData<T>);
+
+impl<'de, T, const N: usize> Visitor<'de> for ArrayVisitor<T, N>
+  where T: Deserialize<'de>
+{
+  type Value = [T; N];
+
+  fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "an array of length {}", N)
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<[T; N], A::Error> {
+    let mut failure = None;
+    let elements: [Option<T>; N] = core::array::from_fn(|i| {
+      if failure.is_some() {
+        return None;
+      }
+      match seq.next_element() {
+        Ok(Some(element)) => Some(element),
+        Ok(None) => {
+          failure = Some(A::Error::invalid_length(i, &self));
+          None
+        }
+        Err(err) => {
+          failure = Some(err);
+          None
+        }
+      }
+    });
+    match failure {
+      Some(err) => Err(err),
+      // every element is present once no failure was recorded
+      None => Ok(elements.map(|element| element.unwrap())),
+    }
+  }
+}
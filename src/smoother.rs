@@ -0,0 +1,329 @@
+use crate::{KalmanError, KalmanState, Scalar};
+
+/// Forward filter and backward smoother results for one observation
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RtsStep<T> {
+  /// Estimate after the predict step, before incorporating the observation
+  pub prior_estimate: T,
+  /// Uncertainty after the predict step
+  pub prior_uncertainty: T,
+  /// Causal (forward) estimate after incorporating the observation
+  pub estimate: T,
+  /// Causal (forward) uncertainty after incorporating the observation
+  pub uncertainty: T,
+  /// Smoothed estimate, using all observations before and after this one
+  pub smoothed_estimate: T,
+  /// Smoothed uncertainty
+  pub smoothed_uncertainty: T,
+}
+
+impl<T> RtsStep<T>
+  where T: Scalar
+{
+  pub fn zero() -> Self {
+    RtsStep {
+      prior_estimate: T::zero(),
+      prior_uncertainty: T::zero(),
+      estimate: T::zero(),
+      uncertainty: T::zero(),
+      smoothed_estimate: T::zero(),
+      smoothed_uncertainty: T::zero(),
+    }
+  }
+}
+
+/// Rauch-Tung-Striebel smoother for a recorded series of observations.
+/// Runs the forward filter from `initial` (one predict and one update per
+/// observation), storing the per-step priors and posteriors in `steps`,
+/// then runs the backward pass to fill in the smoothed estimates.
+/// `steps` must be at least as long as `observations`; any extra entries
+/// are left untouched. No allocation is performed.
+pub fn rts_smooth<T>(initial: &KalmanState<T>, observations: &[T], steps: &mut [RtsStep<T>])
+  -> Result<(), KalmanError>
+  where T: Scalar
+{
+  if steps.len() < observations.len() {
+    return Err(KalmanError::BufferTooSmall);
+  }
+  let steps = &mut steps[..observations.len()];
+
+  // forward pass
+  let mut state = *initial;
+  for (step, &observation) in steps.iter_mut().zip(observations) {
+    state.predict();
+    step.prior_estimate = state.estimate;
+    step.prior_uncertainty = state.uncertainty;
+    state.update(observation);
+    step.estimate = state.estimate;
+    step.uncertainty = state.uncertainty;
+    step.smoothed_estimate = state.estimate;
+    step.smoothed_uncertainty = state.uncertainty;
+  }
+
+  // backward pass: the last step is already smoothed
+  for k in (0..steps.len().saturating_sub(1)).rev() {
+    let next = steps[k + 1];
+    smooth_step(&mut steps[k], &next);
+  }
+  Ok(())
+}
+
+/// One step of the RTS backward recursion: fill in the smoothed values
+/// of `step` from the already-smoothed `next` step.
+fn smooth_step<T>(step: &mut RtsStep<T>, next: &RtsStep<T>)
+  where T: Scalar
+{
+  if next.prior_uncertainty == T::zero() {
+    step.smoothed_estimate = step.estimate;
+    step.smoothed_uncertainty = step.uncertainty;
+    return;
+  }
+  let smoother_gain = step.uncertainty / next.prior_uncertainty;
+
+  // x_s = x + C (x_s' - x'), computed without negative intermediates
+  step.smoothed_estimate =
+    if next.smoothed_estimate >= next.prior_estimate {
+      step.estimate + smoother_gain * (next.smoothed_estimate - next.prior_estimate)
+    }
+    else {
+      step.estimate - smoother_gain * (next.prior_estimate - next.smoothed_estimate)
+    };
+  // P_s = P + C^2 (P_s' - P'), where normally P_s' <= P'
+  let gain_sq = smoother_gain * smoother_gain;
+  step.smoothed_uncertainty =
+    if next.smoothed_uncertainty >= next.prior_uncertainty {
+      step.uncertainty + gain_sq * (next.smoothed_uncertainty - next.prior_uncertainty)
+    }
+    else {
+      step.uncertainty - gain_sq * (next.prior_uncertainty - next.smoothed_uncertainty)
+    };
+}
+
+/// A fixed-lag smoother: publishes the estimate from `LAG` observations ago,
+/// smoothed using every observation received since. This trades a fixed
+/// delay for a much cleaner estimate than the causal filter provides.
+/// The history is kept in a ring buffer sized at compile time (no allocation).
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "FixedLagData<T, LAG>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct FixedLagSmoother<T, const LAG: usize> {
+  /// The causal (forward) filter state
+  pub state: KalmanState<T>,
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+  history: [RtsStep<T>; LAG],
+  head: usize,  // index of the oldest step in history
+  len: usize,   // number of valid steps in history
+}
+
+/// Unvalidated `FixedLagSmoother` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(deserialize = "T: Scalar + serde::Deserialize<'de>"))]
+struct FixedLagData<T, const LAG: usize> {
+  state: KalmanState<T>,
+  #[serde(with = "crate::serde_array")]
+  history: [RtsStep<T>; LAG],
+  head: usize,
+  len: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<T, const LAG: usize> TryFrom<FixedLagData<T, LAG>> for FixedLagSmoother<T, LAG>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: FixedLagData<T, LAG>) -> Result<Self, Self::Error> {
+    // with no lag there is no history, so the head must stay at zero
+    if data.len > LAG || data.head >= LAG.max(1) {
+      return Err("history index out of range for lag");
+    }
+    Ok(FixedLagSmoother {
+      state: data.state,
+      history: data.history,
+      head: data.head,
+      len: data.len,
+    })
+  }
+}
+
+impl<T, const LAG: usize> FixedLagSmoother<T, LAG>
+  where T: Scalar
+{
+  pub fn new(initial: KalmanState<T>) -> Self {
+    FixedLagSmoother {
+      state: initial,
+      history: [RtsStep::zero(); LAG],
+      head: 0,
+      len: 0,
+    }
+  }
+
+  /// Run one predict and update step of the causal filter with `observation`.
+  /// Once `LAG` earlier observations have been received, returns the step
+  /// from `LAG` observations ago with its smoothed estimate filled in.
+  pub fn update(&mut self, observation: T) -> Option<RtsStep<T>> {
+    self.state.predict();
+    let prior_estimate = self.state.estimate;
+    let prior_uncertainty = self.state.uncertainty;
+    self.state.update(observation);
+    let latest = RtsStep {
+      prior_estimate,
+      prior_uncertainty,
+      estimate: self.state.estimate,
+      uncertainty: self.state.uncertainty,
+      smoothed_estimate: self.state.estimate,
+      smoothed_uncertainty: self.state.uncertainty,
+    };
+
+    // backward pass over the window, newest to oldest
+    let mut next = latest;
+    for i in (0..self.len).rev() {
+      let idx = (self.head + i) % LAG;
+      smooth_step(&mut self.history[idx], &next);
+      next = self.history[idx];
+    }
+    let lagged = if self.len == LAG { Some(next) } else { None };
+
+    // push the latest step, evicting the oldest when full
+    if LAG > 0 {
+      if self.len == LAG {
+        self.history[self.head] = latest;
+        self.head = (self.head + 1) % LAG;
+      }
+      else {
+        self.history[(self.head + self.len) % LAG] = latest;
+        self.len += 1;
+      }
+    }
+    lagged
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+
+  #[test]
+  fn test_smoother_reduces_error_f64() {
+    // a constant truth with alternating measurement noise
+    const COUNT: usize = 40;
+    let mut observations = [0.0f64; COUNT];
+    for (i, observation) in observations.iter_mut().enumerate() {
+      *observation = 5.0 + if i % 2 == 0 { 0.3 } else { -0.3 } + (i % 5) as f64 * 0.02;
+    }
+    let initial = KalmanState::new(0.0f64, 100.0, 0.09, 1E-4);
+    let mut steps = [RtsStep::zero(); COUNT];
+    rts_smooth(&initial, &observations, &mut steps).unwrap();
+
+    let forward_error: f64 = steps.iter().map(|s| (s.estimate - 5.04).abs()).sum();
+    let smoothed_error: f64 = steps.iter().map(|s| (s.smoothed_estimate - 5.04).abs()).sum();
+    println!("forward: {} smoothed: {}", forward_error, smoothed_error);
+    assert!(smoothed_error < forward_error / 2.0);
+
+    for step in steps.iter() {
+      assert!(step.smoothed_uncertainty <= step.uncertainty + 1E-15);
+      assert!(step.uncertainty <= step.prior_uncertainty);
+    }
+    // the final smoothed step is the forward estimate
+    assert_eq!(steps[COUNT - 1].smoothed_estimate, steps[COUNT - 1].estimate);
+  }
+
+  #[test]
+  fn test_smoother_i32f32() {
+    type TestType = I32F32;
+    let observations = [1.0, 1.2, 0.8, 1.1, 0.9, 1.0].map(TestType::from_num);
+    let initial = KalmanState::new(
+      TestType::from_num(0),
+      TestType::from_num(10),
+      TestType::from_num(0.04),
+      TestType::from_num(1E-3),
+    );
+    let mut steps = [RtsStep::zero(); 8];
+    rts_smooth(&initial, &observations, &mut steps).unwrap();
+    println!("first: {} smoothed: {}", steps[0].estimate, steps[0].smoothed_estimate);
+    // the first forward estimate sees only one reading; the smoothed one sees all
+    assert!((steps[0].smoothed_estimate - TestType::from_num(1)).abs() < TestType::from_num(0.05));
+    assert_eq!(steps[7], RtsStep::zero());
+
+    let mut short = [RtsStep::zero(); 2];
+    assert_eq!(rts_smooth(&initial, &observations, &mut short), Err(KalmanError::BufferTooSmall));
+  }
+
+  #[test]
+  fn test_fixed_lag_matches_rts_f64() {
+    const COUNT: usize = 30;
+    const LAG: usize = 5;
+    let mut observations = [0.0f64; COUNT];
+    for (i, observation) in observations.iter_mut().enumerate() {
+      *observation = 2.0 + if i % 3 == 0 { 0.4 } else { -0.2 };
+    }
+    let initial = KalmanState::new(0.0f64, 10.0, 0.1, 1E-3);
+    let mut smoother: FixedLagSmoother<f64, LAG> = FixedLagSmoother::new(initial);
+
+    for (i, &observation) in observations.iter().enumerate() {
+      let lagged = smoother.update(observation);
+      if i < LAG {
+        assert!(lagged.is_none());
+        continue;
+      }
+      // the lagged output equals a full RTS pass over the data so far
+      let mut steps = [RtsStep::zero(); COUNT];
+      rts_smooth(&initial, &observations[..=i], &mut steps).unwrap();
+      let lagged = lagged.unwrap();
+      assert!((lagged.smoothed_estimate - steps[i - LAG].smoothed_estimate).abs() < 1E-12);
+      assert!((lagged.smoothed_uncertainty - steps[i - LAG].smoothed_uncertainty).abs() < 1E-12);
+    }
+  }
+
+  #[test]
+  fn test_zero_lag_i32f32() {
+    type TestType = I32F32;
+    let initial = KalmanState::new(
+      TestType::from_num(0),
+      TestType::from_num(1),
+      TestType::from_num(0.1),
+      TestType::from_num(1E-3),
+    );
+    let mut smoother: FixedLagSmoother<TestType, 0> = FixedLagSmoother::new(initial);
+    let lagged = smoother.update(TestType::from_num(1)).unwrap();
+    // with no lag the output is just the causal estimate
+    assert_eq!(lagged.smoothed_estimate, smoother.state.estimate);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_fixed_lag_i32f32() {
+    type TestType = I32F32;
+    let initial = KalmanState::new(
+      TestType::from_num(0),
+      TestType::from_num(1),
+      TestType::from_num(0.1),
+      TestType::from_num(1E-3),
+    );
+    let mut smoother: FixedLagSmoother<TestType, 3> = FixedLagSmoother::new(initial);
+    for i in 0..5 {
+      smoother.update(TestType::from_num(i));
+    }
+    // a checkpointed smoother carries on exactly where the original left off
+    let json = serde_json::to_string(&smoother).unwrap();
+    let mut restored: FixedLagSmoother<TestType, 3> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.update(TestType::from_num(5)), smoother.update(TestType::from_num(5)));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_validates_ring_f64() {
+    let initial = KalmanState::new(0.0f64, 1.0, 0.1, 1E-3);
+    let smoother: FixedLagSmoother<f64, 2> = FixedLagSmoother::new(initial);
+    let json = serde_json::to_string(&smoother).unwrap();
+    let corrupt = json.replace(r#""head":0"#, r#""head":7"#);
+    assert!(serde_json::from_str::<FixedLagSmoother<f64, 2>>(&corrupt).is_err());
+    let corrupt = json.replace(r#""len":0"#, r#""len":3"#);
+    assert!(serde_json::from_str::<FixedLagSmoother<f64, 2>>(&corrupt).is_err());
+  }
+}
@@ -0,0 +1,166 @@
+use crate::scalar::blend;
+use crate::{KalmanState, Scalar};
+
+/// Solve for the steady-state Kalman gain and (posterior) uncertainty of a
+/// single-variable filter that runs one predict and one update per sample.
+/// Returns `(gain, uncertainty)`.
+///
+/// The steady-state prior uncertainty `p` satisfies `p^2 - q p - q r = 0`,
+/// where `q` is the process variance and `r` the measurement variance.
+pub fn steady_state_gain<T>(measurement_variance: T, process_variance: T) -> (T, T)
+  where T: Scalar
+{
+  let r = measurement_variance.abs();
+  let q = process_variance.abs();
+  let two = T::one() + T::one();
+  let four = two + two;
+  // sqrt(q^2 + 4 q r), factored to avoid underflow of q^2 in fixed-point types
+  let root = q.sqrt() * (q + four * r).sqrt();
+  let prior = (q + root) / two;
+  let denom = prior + r;
+  if denom == T::zero() {
+    return (T::zero(), T::zero());
+  }
+  let gain = prior / denom;
+  (gain, gain * r)
+}
+
+/// A single-variable filter that applies a precomputed steady-state gain,
+/// costing a single multiply-add per sample. Once a `KalmanState` has
+/// converged, it behaves identically to running predict and update.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "SteadyStateData<T>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct SteadyStateFilter<T> {
+  /// Estimated value of the variable
+  pub estimate: T,
+  gain: T,
+  uncertainty: T,
+}
+
+/// Unvalidated `SteadyStateFilter` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SteadyStateData<T> {
+  estimate: T,
+  gain: T,
+  uncertainty: T,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<SteadyStateData<T>> for SteadyStateFilter<T>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: SteadyStateData<T>) -> Result<Self, Self::Error> {
+    if data.gain < T::zero() || data.gain > T::one() {
+      return Err("gain outside [0, 1]");
+    }
+    if data.uncertainty < T::zero() {
+      return Err("negative uncertainty");
+    }
+    Ok(SteadyStateFilter { estimate: data.estimate, gain: data.gain, uncertainty: data.uncertainty })
+  }
+}
+
+impl<T> SteadyStateFilter<T>
+  where T: Scalar
+{
+  pub fn new(estimate: T, measurement_variance: T, process_variance: T) -> SteadyStateFilter<T> {
+    let (gain, uncertainty) = steady_state_gain(measurement_variance, process_variance);
+    SteadyStateFilter { estimate, gain, uncertainty }
+  }
+
+  /// Create a steady-state filter from the current estimate
+  /// and noise parameters of a `KalmanState`.
+  pub fn from_kalman(state: &KalmanState<T>) -> SteadyStateFilter<T> {
+    SteadyStateFilter::new(state.estimate, state.measurement_variance, state.process_variance)
+  }
+
+  /// The constant Kalman gain applied to every observation
+  pub fn gain(&self) -> T {
+    self.gain
+  }
+
+  /// The steady-state uncertainty in the estimate after each update
+  pub fn uncertainty(&self) -> T {
+    self.uncertainty
+  }
+
+  /// Incorporate a single observation with the constant gain
+  pub fn update(&mut self, observation: T) {
+    self.estimate = blend(self.estimate, observation, self.gain);
+  }
+}
+
+impl<T> From<&KalmanState<T>> for SteadyStateFilter<T>
+  where T: Scalar
+{
+  fn from(state: &KalmanState<T>) -> Self {
+    SteadyStateFilter::from_kalman(state)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I16F16;
+
+  #[test]
+  fn test_matches_converged_kalman_f64() {
+    let mut kstate = KalmanState::new(0.0f64, 1.0, 1E-2, 1E-4);
+    let mut gain = 0.0;
+    for _i in 0..500 {
+      kstate.predict();
+      gain = kstate.update(1.0).gain;
+    }
+    let (ss_gain, ss_uncertainty) = steady_state_gain(1E-2f64, 1E-4);
+    println!("gain: {} ss gain: {}", gain, ss_gain);
+    assert!((gain - ss_gain).abs() < 1E-9);
+    assert!((kstate.uncertainty - ss_uncertainty).abs() < 1E-9);
+
+    let mut filter = SteadyStateFilter::from(&kstate);
+    let mut reference = kstate;
+    for i in 0..20 {
+      let observation = 1.0 + (i % 4) as f64 * 0.1;
+      filter.update(observation);
+      reference.predict();
+      reference.update(observation);
+    }
+    assert!((filter.estimate - reference.estimate).abs() < 1E-9);
+  }
+
+  #[test]
+  fn test_steady_state_i16f16() {
+    type TestType = I16F16;
+    let measurement_variance = TestType::from_num(1E-2);
+    let process_variance = TestType::from_num(1E-4);
+    let mut filter = SteadyStateFilter::new(TestType::from_num(0), measurement_variance, process_variance);
+    println!("gain: {} uncert: {}", filter.gain(), filter.uncertainty());
+
+    // compare with the f64 solution for the same (quantized) variances
+    let (gain, uncertainty) =
+      steady_state_gain(measurement_variance.to_num::<f64>(), process_variance.to_num::<f64>());
+    assert!((filter.gain().to_num::<f64>() - gain).abs() < 1E-3);
+    assert!((filter.uncertainty().to_num::<f64>() - uncertainty).abs() < 1E-4);
+
+    for _i in 0..200 {
+      filter.update(TestType::from_num(-3));
+    }
+    assert!((filter.estimate - TestType::from_num(-3)).abs() < TestType::from_num(1E-2));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_validates_gain() {
+    let json = r#"{"estimate":1.0,"gain":0.25,"uncertainty":0.01}"#;
+    assert!(serde_json::from_str::<SteadyStateFilter<f64>>(json).is_ok());
+    let json = r#"{"estimate":1.0,"gain":1.5,"uncertainty":0.01}"#;
+    assert!(serde_json::from_str::<SteadyStateFilter<f64>>(json).is_err());
+    let json = r#"{"estimate":1.0,"gain":0.25,"uncertainty":-0.01}"#;
+    assert!(serde_json::from_str::<SteadyStateFilter<f64>>(json).is_err());
+  }
+}
@@ -0,0 +1,405 @@
+use num_traits::Signed;
+
+use crate::{KalmanError, KalmanState, Matrix, Scalar, Vector};
+
+/// Parameters controlling the spread and weighting of sigma points.
+///  - `alpha` sets the spread of the points around the mean (0 < alpha <= 1)
+///  - `beta` incorporates prior knowledge of the distribution (2 is optimal for Gaussian)
+///  - `kappa` is a secondary scaling parameter, usually 0 or 3 - N
+///
+/// Small values of `alpha` produce very large weights, which can overflow
+/// narrow fixed-point types; `alpha = 1` is a safe choice there.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SigmaPointParams<T> {
+  pub alpha: T,
+  pub beta: T,
+  pub kappa: T,
+}
+
+impl<T> SigmaPointParams<T>
+  where T: Scalar
+{
+  /// Panics unless `0 < alpha <= 1`.
+  pub fn new(alpha: T, beta: T, kappa: T) -> Self {
+    assert!(alpha > T::zero() && alpha <= T::one(), "SigmaPointParams alpha must be in (0, 1]");
+    SigmaPointParams { alpha, beta, kappa }
+  }
+
+  /// alpha = 1, beta = 2, kappa = 0: well conditioned for all numeric types
+  pub fn standard() -> Self {
+    SigmaPointParams::new(T::one(), T::one() + T::one(), T::zero())
+  }
+}
+
+/// Weights for the 2N + 1 sigma points of an `N`-state filter
+#[derive(Debug, Clone, Copy)]
+struct SigmaWeights<T> {
+  mean_center: T,
+  covariance_center: T,
+  outer: T,
+  spread: T,
+}
+
+impl<T> SigmaWeights<T>
+  where T: Scalar
+{
+  /// Returns `None` if `alpha` is outside `(0, 1]` or the sigma point
+  /// spread `n + lambda = alpha^2 (N + kappa)` is not positive.
+  fn new<const N: usize>(params: &SigmaPointParams<T>) -> Option<Self> {
+    if params.alpha <= T::zero() || params.alpha > T::one() {
+      return None;
+    }
+    let n = T::from_f64(N as f64);
+    let alpha_sq = params.alpha * params.alpha;
+    let lambda = alpha_sq * (n + params.kappa) - n;
+    let n_lambda = n + lambda;
+    if n_lambda <= T::zero() {
+      return None;
+    }
+    let mean_center = lambda / n_lambda;
+    Some(SigmaWeights {
+      mean_center,
+      covariance_center: mean_center + (T::one() - alpha_sq + params.beta),
+      outer: T::one() / (n_lambda + n_lambda),
+      spread: n_lambda.sqrt(),
+    })
+  }
+}
+
+/// The 2N + 1 sigma points: the mean, plus N points on each side of it
+#[derive(Debug, Clone, Copy)]
+struct SigmaPoints<T, const N: usize> {
+  center: Vector<T, N>,
+  plus: [Vector<T, N>; N],
+  minus: [Vector<T, N>; N],
+}
+
+/// An unscented Kalman filter with `N` state variables and `M` measured variables.
+/// Rather than linearizing, it propagates a deterministic set of sigma points
+/// through the user-supplied nonlinear models, which copes with strongly
+/// nonlinear observations where an extended Kalman filter breaks down.
+/// Requires a signed Scalar type, since sigma points spread either side of the mean.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "UnscentedFilterData<T, N, M>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct UnscentedFilter<T, const N: usize, const M: usize> {
+  /// Estimated state x
+  pub state: Vector<T, N>,
+  /// Estimated state covariance P
+  pub covariance: Matrix<T, N, N>,
+  /// Process noise covariance Q
+  pub process_noise: Matrix<T, N, N>,
+  /// Measurement noise covariance R
+  pub measurement_noise: Matrix<T, M, M>,
+  params: SigmaPointParams<T>,
+  #[cfg_attr(feature = "serde", serde(skip))]
+  weights: SigmaWeights<T>,  // derived from params
+}
+
+/// Unvalidated `UnscentedFilter` fields, as read by serde:
+/// the weights are recomputed from the params
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct UnscentedFilterData<T, const N: usize, const M: usize> {
+  state: Vector<T, N>,
+  covariance: Matrix<T, N, N>,
+  process_noise: Matrix<T, N, N>,
+  measurement_noise: Matrix<T, M, M>,
+  params: SigmaPointParams<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T, const N: usize, const M: usize> TryFrom<UnscentedFilterData<T, N, M>> for UnscentedFilter<T, N, M>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: UnscentedFilterData<T, N, M>) -> Result<Self, Self::Error> {
+    let weights = SigmaWeights::new::<N>(&data.params)
+      .ok_or("sigma point params need 0 < alpha <= 1 and N + kappa > 0")?;
+    Ok(UnscentedFilter {
+      state: data.state,
+      covariance: data.covariance,
+      process_noise: data.process_noise,
+      measurement_noise: data.measurement_noise,
+      params: data.params,
+      weights,
+    })
+  }
+}
+
+impl<T, const N: usize, const M: usize> UnscentedFilter<T, N, M>
+  where T: Scalar + Signed
+{
+  /// Panics if `params.alpha` is outside `(0, 1]`, or if `N + params.kappa`
+  /// is not positive (the sigma points would have no spread).
+  pub fn new(
+    state: Vector<T, N>,
+    covariance: Matrix<T, N, N>,
+    process_noise: Matrix<T, N, N>,
+    measurement_noise: Matrix<T, M, M>,
+    params: SigmaPointParams<T>) -> Self
+  {
+    let weights = SigmaWeights::new::<N>(&params)
+      .expect("UnscentedFilter needs 0 < alpha <= 1 and N + kappa > 0");
+    UnscentedFilter {
+      state,
+      covariance,
+      process_noise,
+      measurement_noise,
+      params,
+      weights,
+    }
+  }
+
+  pub fn params(&self) -> &SigmaPointParams<T> {
+    &self.params
+  }
+
+  fn sigma_points(&self) -> Result<SigmaPoints<T, N>, KalmanError> {
+    let root = self.covariance.cholesky().ok_or(KalmanError::NotPositiveDefinite)?;
+    let mut points = SigmaPoints {
+      center: self.state,
+      plus: [self.state; N],
+      minus: [self.state; N],
+    };
+    for col in 0..N {
+      for row in 0..N {
+        let offset = self.weights.spread * root[(row, col)];
+        points.plus[col][(row, 0)] = self.state[(row, 0)] + offset;
+        points.minus[col][(row, 0)] = self.state[(row, 0)] - offset;
+      }
+    }
+    Ok(points)
+  }
+
+  /// Predict step: propagate the sigma points through the transition function `f`.
+  /// Fails if the covariance is not positive semi-definite,
+  /// in which case the filter is left unchanged.
+  pub fn predict<F>(&mut self, f: F) -> Result<(), KalmanError>
+    where F: Fn(&Vector<T, N>) -> Vector<T, N>
+  {
+    let points = self.sigma_points()?;
+    let w = self.weights;
+    let center = f(&points.center);
+    let mut plus = [center; N];
+    let mut minus = [center; N];
+    for i in 0..N {
+      plus[i] = f(&points.plus[i]);
+      minus[i] = f(&points.minus[i]);
+    }
+
+    let mut mean = center.scale(w.mean_center);
+    for i in 0..N {
+      mean = mean + (plus[i] + minus[i]).scale(w.outer);
+    }
+
+    let deviation = center - mean;
+    let mut covariance = (deviation * deviation.transpose()).scale(w.covariance_center);
+    for i in 0..N {
+      let dp = plus[i] - mean;
+      let dm = minus[i] - mean;
+      covariance = covariance + (dp * dp.transpose() + dm * dm.transpose()).scale(w.outer);
+    }
+
+    self.state = mean;
+    self.covariance = covariance + self.process_noise;
+    Ok(())
+  }
+
+  /// Update step: propagate the sigma points through the observation function `h`
+  /// and correct the state with the measurement.
+  /// Fails if the covariance is not positive semi-definite or the
+  /// innovation covariance is singular, in which case the filter is left unchanged.
+  pub fn update<H>(&mut self, measurement: &Vector<T, M>, h: H) -> Result<(), KalmanError>
+    where H: Fn(&Vector<T, N>) -> Vector<T, M>
+  {
+    let points = self.sigma_points()?;
+    let w = self.weights;
+    let center = h(&points.center);
+    let mut plus = [center; N];
+    let mut minus = [center; N];
+    for i in 0..N {
+      plus[i] = h(&points.plus[i]);
+      minus[i] = h(&points.minus[i]);
+    }
+
+    let mut predicted = center.scale(w.mean_center);
+    for i in 0..N {
+      predicted = predicted + (plus[i] + minus[i]).scale(w.outer);
+    }
+
+    let z_dev = center - predicted;
+    let x_dev = points.center - self.state;
+    let mut innovation_covariance = (z_dev * z_dev.transpose()).scale(w.covariance_center);
+    let mut cross_covariance: Matrix<T, N, M> = (x_dev * z_dev.transpose()).scale(w.covariance_center);
+    for i in 0..N {
+      let zp = plus[i] - predicted;
+      let zm = minus[i] - predicted;
+      let xp = points.plus[i] - self.state;
+      let xm = points.minus[i] - self.state;
+      innovation_covariance = innovation_covariance
+        + (zp * zp.transpose() + zm * zm.transpose()).scale(w.outer);
+      cross_covariance = cross_covariance
+        + (xp * zp.transpose() + xm * zm.transpose()).scale(w.outer);
+    }
+    innovation_covariance = innovation_covariance + self.measurement_noise;
+
+    let inv = innovation_covariance.inverse().ok_or(KalmanError::SingularMatrix)?;
+    let gain: Matrix<T, N, M> = cross_covariance * inv;
+    let correction: Vector<T, N> = gain * (*measurement - predicted);
+    self.state = self.state + correction;
+    self.covariance = self.covariance - gain * innovation_covariance * gain.transpose();
+    Ok(())
+  }
+}
+
+impl<T> UnscentedFilter<T, 1, 1>
+  where T: Scalar + Signed
+{
+  /// Create a single-variable filter from an existing `KalmanState`,
+  /// taking over its estimate, uncertainty and noise parameters.
+  pub fn from_kalman(state: &KalmanState<T>, params: SigmaPointParams<T>) -> Self {
+    UnscentedFilter::new(
+      Matrix::new([[state.estimate]]),
+      Matrix::new([[state.uncertainty]]),
+      Matrix::new([[state.process_variance]]),
+      Matrix::new([[state.measurement_variance]]),
+      params,
+    )
+  }
+
+  /// Convert a single-variable filter back into a `KalmanState`
+  pub fn to_kalman(&self) -> KalmanState<T> {
+    KalmanState {
+      estimate: self.state[(0, 0)],
+      uncertainty: self.covariance[(0, 0)],
+      measurement_variance: self.measurement_noise[(0, 0)],
+      process_variance: self.process_noise[(0, 0)],
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::I32F32;
+
+  #[test]
+  fn test_linear_matches_kalman_f64() {
+    // for a linear model the unscented transform is exact
+    let kstate = KalmanState::new(1.0f64, 0.5, 0.2, 0.01);
+    let mut expected = kstate;
+    let mut filter = UnscentedFilter::from_kalman(&kstate, SigmaPointParams::new(1E-3, 2.0, 0.0));
+
+    for i in 0..10 {
+      let observation = 2.0 + i as f64 * 0.05;
+      expected.predict();
+      expected.update(observation);
+      filter.predict(|x| *x).unwrap();
+      filter.update(&Matrix::new([[observation]]), |x| *x).unwrap();
+    }
+    let result = filter.to_kalman();
+    assert!((result.estimate - expected.estimate).abs() < 1E-9);
+    assert!((result.uncertainty - expected.uncertainty).abs() < 1E-9);
+  }
+
+  #[test]
+  fn test_range_bearing_f64() {
+    // a stationary 2D point observed by range and bearing from the origin
+    let h = |x: &Vector<f64, 2>| {
+      let r2 = x[(0, 0)] * x[(0, 0)] + x[(1, 0)] * x[(1, 0)];
+      Matrix::new([[r2.sqrt()], [x[(1, 0)].atan2(x[(0, 0)])]])
+    };
+    let truth = Matrix::new([[3.0], [4.0]]);
+    let measurement = h(&truth);
+
+    let mut filter = UnscentedFilter::new(
+      Matrix::new([[4.0], [3.0]]),
+      Matrix::new([[1.0, 0.0], [0.0, 1.0]]),
+      Matrix::new([[1E-4, 0.0], [0.0, 1E-4]]),
+      Matrix::new([[1E-4, 0.0], [0.0, 1E-4]]),
+      SigmaPointParams::standard(),
+    );
+    for _i in 0..30 {
+      filter.predict(|x| *x).unwrap();
+      filter.update(&measurement, h).unwrap();
+    }
+    println!("state: {:?}", filter.state);
+    assert!((filter.state[(0, 0)] - 3.0).abs() < 1E-3);
+    assert!((filter.state[(1, 0)] - 4.0).abs() < 1E-3);
+  }
+
+  #[test]
+  fn test_cubic_observation_i32f32() {
+    type TestType = I32F32;
+    let mut filter = UnscentedFilter::new(
+      Matrix::new([[TestType::from_num(1.5)]]),
+      Matrix::new([[TestType::from_num(0.25)]]),
+      Matrix::new([[TestType::from_num(1E-3)]]),
+      Matrix::new([[TestType::from_num(1E-2)]]),
+      SigmaPointParams::standard(),
+    );
+    let measurement = Matrix::new([[TestType::from_num(8)]]);
+    for _i in 0..30 {
+      filter.predict(|x| *x).unwrap();
+      filter.update(&measurement, |x| Matrix::new([[x[(0, 0)] * x[(0, 0)] * x[(0, 0)]]])).unwrap();
+    }
+    println!("state: {}", filter.state[(0, 0)]);
+    assert!((filter.state[(0, 0)] - TestType::from_num(2)).abs() < TestType::from_num(1E-2));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_recomputes_weights_f64() {
+    let filter = UnscentedFilter::from_kalman(
+      &KalmanState::new(1.0f64, 0.5, 0.2, 0.01), SigmaPointParams::standard());
+    let json = serde_json::to_string(&filter).unwrap();
+    assert!(!json.contains("weights"));
+
+    // retuning alpha in the config takes effect on load
+    let retuned = json.replace(r#""alpha":1.0"#, r#""alpha":0.5"#);
+    let restored: UnscentedFilter<f64, 1, 1> = serde_json::from_str(&retuned).unwrap();
+    let expected = SigmaWeights::new::<1>(&SigmaPointParams::new(0.5, 2.0, 0.0)).unwrap();
+    assert_eq!(restored.params().alpha, 0.5);
+    assert_eq!(restored.weights.spread, expected.spread);
+    assert_eq!(restored.weights.covariance_center, expected.covariance_center);
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_zero_alpha_f64() {
+    SigmaPointParams::new(0.0f64, 2.0, 0.0);
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_degenerate_kappa_i32f32() {
+    type TestType = I32F32;
+    let one = TestType::from_num(1);
+    // kappa = -N leaves the sigma points no spread
+    UnscentedFilter::new(
+      Matrix::new([[one]]),
+      Matrix::new([[one]]),
+      Matrix::new([[one]]),
+      Matrix::new([[one]]),
+      SigmaPointParams::new(one, TestType::from_num(2), TestType::from_num(-1)),
+    );
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_rejects_bad_params_f64() {
+    let filter = UnscentedFilter::from_kalman(
+      &KalmanState::new(1.0f64, 0.5, 0.2, 0.01), SigmaPointParams::standard());
+    let json = serde_json::to_string(&filter).unwrap();
+    assert!(serde_json::from_str::<UnscentedFilter<f64, 1, 1>>(&json).is_ok());
+    let corrupt = json.replace(r#""alpha":1.0"#, r#""alpha":0.0"#);
+    assert!(serde_json::from_str::<UnscentedFilter<f64, 1, 1>>(&corrupt).is_err());
+    let corrupt = json.replace(r#""kappa":0.0"#, r#""kappa":-1.0"#);
+    assert!(serde_json::from_str::<UnscentedFilter<f64, 1, 1>>(&corrupt).is_err());
+  }
+}
@@ -0,0 +1,241 @@
+use crate::scalar::signed_diff;
+use crate::{KalmanState, Scalar, UpdateReport};
+
+/// A single-variable Kalman filter for wrapped (circular) quantities such as
+/// a compass heading (modulus 360) or an oscillator phase (modulus 1 cycle).
+/// The estimate is kept in `[0, modulus)`, and each residual takes the shortest
+/// way around the circle, so readings either side of the wrap point fuse correctly.
+/// Unsigned types are supported, provided `modulus` is representable.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+  try_from = "WrappedStateData<T>",
+  bound(deserialize = "T: Scalar + serde::Deserialize<'de>")))]
+pub struct WrappedState<T> {
+  /// The underlying filter state, with the estimate kept in `[0, modulus)`
+  pub state: KalmanState<T>,
+  modulus: T,
+}
+
+/// Unvalidated `WrappedState` fields, as read by serde
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(deserialize = "T: Scalar + serde::Deserialize<'de>"))]
+struct WrappedStateData<T> {
+  state: KalmanState<T>,
+  modulus: T,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<WrappedStateData<T>> for WrappedState<T>
+  where T: Scalar
+{
+  type Error = &'static str;
+
+  fn try_from(data: WrappedStateData<T>) -> Result<Self, Self::Error> {
+    if data.modulus <= T::zero() {
+      return Err("modulus must be positive");
+    }
+    if data.state.estimate < T::zero() || data.state.estimate >= data.modulus {
+      return Err("estimate outside [0, modulus)");
+    }
+    Ok(WrappedState { state: data.state, modulus: data.modulus })
+  }
+}
+
+/// Wrap `value` into `[0, modulus)`
+fn wrap<T: Scalar>(value: T, modulus: T) -> T {
+  let mut wrapped = value % modulus;
+  if wrapped < T::zero() {
+    wrapped = wrapped + modulus;
+  }
+  // guard against rounding up to the modulus itself
+  if wrapped >= modulus { T::zero() } else { wrapped }
+}
+
+impl<T> WrappedState<T>
+  where T: Scalar
+{
+  /// Panics if `modulus` is zero.
+  pub fn new(
+    estimate: T,
+    uncertainty: T,
+    measurement_variance: T,
+    process_variance: T,
+    modulus: T) -> WrappedState<T>
+  {
+    let modulus = modulus.abs();
+    assert!(modulus != T::zero(), "WrappedState modulus must be non-zero");
+    WrappedState {
+      state: KalmanState::new(
+        wrap(estimate, modulus), uncertainty, measurement_variance, process_variance),
+      modulus,
+    }
+  }
+
+  pub fn modulus(&self) -> T {
+    self.modulus
+  }
+
+  /// Predict step: see `KalmanState::predict`
+  pub fn predict(&mut self) {
+    self.state.predict();
+  }
+
+  /// Predict step with a variable time step: see `KalmanState::predict_dt`
+  pub fn predict_dt(&mut self, dt: T) {
+    self.state.predict_dt(dt);
+  }
+
+  /// Update step: incorporate a single (possibly unwrapped) observation,
+  /// taking the shortest way around the circle to it.
+  pub fn update(&mut self, observation: T) -> UpdateReport<T> {
+    self.update_with_variance(observation, self.state.measurement_variance)
+  }
+
+  /// Update step, using the given measurement variance
+  /// for this observation instead of the one configured at construction.
+  pub fn update_with_variance(&mut self, observation: T, measurement_variance: T) -> UpdateReport<T> {
+    let modulus = self.modulus;
+    let half = modulus / (T::one() + T::one());
+    let observation = wrap(observation, modulus);
+    let estimate = self.state.estimate;
+
+    let innovation_variance = self.state.uncertainty + measurement_variance.abs();
+    let kalman_gain = self.state.uncertainty / innovation_variance;
+
+    // distance going forward (increasing) around the circle, in [0, modulus)
+    let forward =
+      if observation >= estimate { observation - estimate }
+      else { modulus - (estimate - observation) };
+
+    // step along the shorter arc, without forming negative intermediates
+    let (new_estimate, innovation) =
+      if forward <= half {
+        let step = kalman_gain * forward;
+        let room = modulus - estimate;
+        let new_estimate = if step >= room { step - room } else { estimate + step };
+        (new_estimate, forward)
+      }
+      else {
+        let backward = modulus - forward;
+        let step = kalman_gain * backward;
+        let new_estimate = if step > estimate { modulus - (step - estimate) } else { estimate - step };
+        (new_estimate, signed_diff(T::zero(), backward))
+      };
+
+    self.state.estimate = wrap(new_estimate, modulus);
+    self.state.uncertainty = (T::one() - kalman_gain) * self.state.uncertainty;
+
+    UpdateReport {
+      innovation,
+      innovation_variance,
+      gain: kalman_gain,
+      residual: (T::one() - kalman_gain) * innovation,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fixed::types::{I16F16, U16F16};
+
+  #[test]
+  fn test_heading_across_north_f64() {
+    let mut heading = WrappedState::new(350.0f64, 100.0, 4.0, 0.01, 360.0);
+    // readings scattered either side of due north
+    let readings = [355.0, 5.0, 0.0, 358.0, 2.0, 360.0, -1.0, 1.0];
+    for _j in 0..10 {
+      for &reading in readings.iter() {
+        heading.predict();
+        heading.update(reading);
+      }
+    }
+    println!("heading: {}", heading.state.estimate);
+    let estimate = heading.state.estimate;
+    let error = if estimate > 180.0 { 360.0 - estimate } else { estimate };
+    assert!(error < 1.0);
+
+    // a naive linear filter would settle near 180 instead
+    let mut linear = KalmanState::new(350.0f64, 100.0, 4.0, 0.01);
+    for &reading in readings.iter() {
+      linear.update(reading);
+    }
+    assert!(linear.estimate > 100.0 && linear.estimate < 300.0);
+  }
+
+  #[test]
+  fn test_report_innovation_f64() {
+    let mut heading = WrappedState::new(10.0f64, 1.0, 1.0, 0.0, 360.0);
+    let report = heading.update(350.0);
+    assert!((report.innovation - -20.0).abs() < 1E-12);
+    assert!((report.residual - -10.0).abs() < 1E-12);
+    assert!((heading.state.estimate - 360.0).abs() < 1E-9 || heading.state.estimate.abs() < 1E-9);
+  }
+
+  #[test]
+  fn test_phase_wrap_i16f16() {
+    type TestType = I16F16;
+    // phase in cycles, modulus one
+    let mut phase = WrappedState::new(
+      TestType::from_num(0.9),
+      TestType::from_num(1),
+      TestType::from_num(0.01),
+      TestType::from_num(0),
+      TestType::from_num(1),
+    );
+    for _i in 0..10 {
+      phase.update(TestType::from_num(0.05));
+    }
+    println!("phase: {}", phase.state.estimate);
+    assert!((phase.state.estimate - TestType::from_num(0.05)).abs() < TestType::from_num(1E-2));
+  }
+
+  #[test]
+  fn test_heading_unsigned_u16f16() {
+    type TestType = U16F16;
+    let mut heading = WrappedState::new(
+      TestType::from_num(5),
+      TestType::from_num(100),
+      TestType::from_num(1),
+      TestType::from_num(0),
+      TestType::from_num(360),
+    );
+    // the shortest way from 5 to 355 is backwards through zero
+    for _i in 0..10 {
+      heading.update(TestType::from_num(355));
+    }
+    println!("heading: {}", heading.state.estimate);
+    assert!(heading.state.estimate.abs_diff(TestType::from_num(355)) < TestType::from_num(0.1));
+  }
+
+  #[test]
+  #[should_panic(expected = "modulus must be non-zero")]
+  fn test_zero_modulus_i16f16() {
+    type TestType = I16F16;
+    let zero = TestType::from_num(0);
+    WrappedState::new(zero, zero, zero, zero, zero);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn test_serde_validates_modulus() {
+    let heading = WrappedState::new(10.0f64, 1.0, 4.0, 0.01, 360.0);
+    let json = serde_json::to_string(&heading).unwrap();
+    assert!(serde_json::from_str::<WrappedState<f64>>(&json).is_ok());
+    for corrupt in [r#""modulus":0.0"#, r#""modulus":-360.0"#, r#""modulus":5.0"#] {
+      let corrupt = json.replace(r#""modulus":360.0"#, corrupt);
+      assert!(serde_json::from_str::<WrappedState<f64>>(&corrupt).is_err());
+    }
+
+    type TestType = I16F16;
+    let heading = WrappedState::new(
+      TestType::from_num(10), TestType::from_num(1), TestType::from_num(4), TestType::from_num(0.01),
+      TestType::from_num(360));
+    let json = serde_json::to_string(&heading).unwrap();
+    let corrupt = json.replace(&serde_json::to_string(&TestType::from_num(360)).unwrap(), r#""0""#);
+    assert_ne!(corrupt, json);
+    assert!(serde_json::from_str::<WrappedState<TestType>>(&corrupt).is_err());
+  }
+}